@@ -11,12 +11,525 @@ pub(crate) mod db {
 
     #[allow(dead_code)]
     pub fn setup() -> Result<SnakeDb, ShahError> {
-        SnakeDb::new("detail")
+        let mut db = SnakeDb::new("detail")?;
+        db.set_compress(true);
+        Ok(db)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::models::ExampleError;
+        use shah::db::entity::Entity;
+        use shah::db::snake::{SnakeClient, SnakeHead};
+        use shah::models::{GeneId, Worker};
+        use shah::{BLOCK_SIZE, ClientError, Taker};
+
+        fn frees(db: &mut SnakeDb) -> Vec<(u64, u64)> {
+            let mut page = [SnakeHead::default(); 16];
+            let n = db.index.list(GeneId(1), &mut page).expect("list");
+            let mut frees: Vec<_> = page[..n]
+                .iter()
+                .filter(|h| h.entity_flags().is_alive() && h.flags.is_free())
+                .map(|h| (h.position, h.capacity))
+                .collect();
+            frees.sort();
+            frees
+        }
+
+        #[test]
+        fn realloc() {
+            let _ = std::fs::remove_dir_all("data/tests/snake-realloc");
+            let mut db = SnakeDb::new("tests/snake-realloc").unwrap();
+
+            let mut a = SnakeHead::default();
+            let mut b = SnakeHead::default();
+            db.alloc(1000, &mut a).expect("alloc");
+            db.alloc(1000, &mut b).expect("alloc");
+            let (ag, bg) = (a.gene, b.gene);
+            db.write(&ag, &mut a, 0, &[7; 1000]).expect("write");
+
+            // the last snake grows into the end of the file
+            db.realloc(&bg, 3000, &mut b).expect("grow at end");
+            assert_eq!((b.gene, b.capacity), (bg, 3000));
+
+            // no room after a, so it is moved
+            let position = a.position;
+            db.realloc(&ag, 1500, &mut a).expect("grow moved");
+            assert_eq!(a.gene, ag);
+            assert_ne!(a.position, position);
+            let mut buf = [0u8; 1000];
+            db.read(&ag, &mut a, 0, &mut buf).expect("read");
+            assert!(buf.iter().all(|x| *x == 7));
+            assert_eq!(frees(&mut db), [(position, 1000)]);
+
+            // shrinking splits the tail off and it is merged with its
+            // neighbour, which the next grow takes in place
+            db.realloc(&bg, 2000, &mut b).expect("shrink");
+            assert_eq!(
+                frees(&mut db),
+                [(position, 1000), (b.position + 2000, 1000)]
+            );
+            db.realloc(&bg, 2500, &mut b).expect("grow in place");
+            assert_eq!(
+                frees(&mut db),
+                [(position, 1000), (b.position + 2500, 500)]
+            );
+
+            let mut c = SnakeHead::default();
+            db.alloc(900, &mut c).expect("alloc");
+            assert_eq!(c.position, position);
+        }
+
+        #[test]
+        fn punch_hole() {
+            let _ = std::fs::remove_dir_all("data/tests/snake-punch");
+            let mut db = SnakeDb::new("tests/snake-punch").unwrap();
+            db.set_punch_hole(64 * 1024);
+
+            let size = 1024 * 1024;
+            let mut big = SnakeHead::default();
+            let mut small = SnakeHead::default();
+            db.alloc(size, &mut big).expect("alloc");
+            db.alloc(100, &mut small).expect("alloc");
+            let gene = big.gene;
+            let data = vec![1u8; size as usize];
+            db.write(&gene, &mut big, 0, &data).expect("write");
+
+            let before = db.stats().expect("stats");
+            assert!(before.disk >= size);
+
+            db.free(&gene).expect("free");
+            let after = db.stats().expect("stats");
+            assert_eq!(after.size, before.size);
+            assert_eq!((after.free, after.free_bytes), (1, size));
+            assert!(after.disk + size - 8192 <= before.disk);
+
+            // the punched region is reused like any other and reads zeros
+            // past the first block, which was not a whole block
+            db.alloc(size, &mut big).expect("alloc again");
+            let gene = big.gene;
+            let mut buf = [9u8; 100];
+            db.read(&gene, &mut big, 8192, &mut buf).expect("read");
+            assert!(buf.iter().all(|b| *b == 0));
+        }
+
+        #[test]
+        fn handle() {
+            use std::io::{Read, Seek, SeekFrom, Write};
+
+            let _ = std::fs::remove_dir_all("data/tests/snake-handle");
+            let mut db = SnakeDb::new("tests/snake-handle").unwrap();
+
+            let mut head = SnakeHead::default();
+            db.alloc(10_000, &mut head).expect("alloc");
+            let data: Vec<u8> = (0..9_000).map(|i| i as u8).collect();
+
+            let mut file = db.open(&head.gene).expect("open");
+            let mut out = Vec::new();
+            file.read_to_end(&mut out).expect("read empty");
+            assert!(out.is_empty());
+
+            file.write_all(&data).expect("write");
+            assert_eq!(file.head().length, 9_000);
+
+            file.rewind().expect("rewind");
+            file.read_to_end(&mut out).expect("read");
+            assert_eq!(out, data);
+
+            let mut tail = [0u8; 10];
+            file.seek(SeekFrom::End(-10)).expect("seek");
+            file.read_exact(&mut tail).expect("read tail");
+            assert_eq!(tail, data[8_990..]);
+            assert!(file.seek(SeekFrom::Current(-10_000)).is_err());
+
+            // writes stop at the capacity
+            file.seek(SeekFrom::Start(9_995)).expect("seek");
+            assert!(file.write_all(&[1; 10]).is_err());
+            assert_eq!(file.head().length, 10_000);
+        }
+
+        #[test]
+        fn dedup() {
+            use std::io::Write;
+
+            let _ = std::fs::remove_dir_all("data/tests/snake-dedup");
+            let mut db = SnakeDb::new("tests/snake-dedup").unwrap();
+            db.set_dedup(true).expect("set dedup");
+
+            let mut a = SnakeHead::default();
+            let mut b = SnakeHead::default();
+            let mut c = SnakeHead::default();
+            db.alloc_dedup(b"same attachment", &mut a).expect("alloc a");
+            db.alloc_dedup(b"same attachment", &mut b).expect("alloc b");
+            db.alloc_dedup(b"other attachment", &mut c).expect("alloc c");
+            assert_eq!(a.gene, b.gene);
+            assert_ne!(a.gene, c.gene);
+            assert_eq!((b.refs, c.refs), (2, 1));
+
+            let gene = a.gene;
+            assert!(db.write(&gene, &mut a, 0, b"x").is_err());
+
+            // a written snake is swapped for the shared one on flush
+            let mut d = SnakeHead::default();
+            db.alloc(100, &mut d).expect("alloc d");
+            let (dg, position) = (d.gene, d.position);
+            let mut file = db.open(&dg).expect("open");
+            file.write_all(b"same attachment").unwrap();
+            assert_eq!(file.head().gene, dg);
+            file.flush().expect("flush");
+            assert_eq!((file.head().gene, file.head().refs), (gene, 3));
+            drop(file);
+            assert_eq!(frees(&mut db), [(position, 100)]);
+
+            // the region is freed with the last owner
+            for _ in 0..2 {
+                db.free(&gene).expect("free");
+            }
+            assert_eq!(frees(&mut db), [(position, 100)]);
+            db.free(&gene).expect("free");
+            assert_eq!(frees(&mut db), [(a.position, 15), (position, 100)]);
+
+            // freed content is not shared again
+            db.alloc_dedup(b"same attachment", &mut a).expect("alloc a");
+            assert_eq!(a.refs, 1);
+
+            // written content is shared on set_length
+            let mut e = SnakeHead::default();
+            db.alloc(100, &mut e).expect("alloc e");
+            let eg = e.gene;
+            db.write(&eg, &mut e, 0, b"same attachment").expect("write");
+            db.set_length(&eg, &mut e, 15).expect("set length");
+            assert_eq!((e.gene, e.refs), (a.gene, 2));
+
+            // a handle is flushed on drop
+            let mut f = SnakeHead::default();
+            db.alloc(100, &mut f).expect("alloc f");
+            let fg = f.gene;
+            db.open(&fg).expect("open").write_all(b"same attachment").unwrap();
+            let shared = e.gene;
+            db.index.get(&shared, &mut e).expect("get e");
+            assert_eq!(e.refs, 3);
+            assert!(db.open(&fg).is_err());
+        }
+
+        #[test]
+        fn compress() {
+            let _ = std::fs::remove_dir_all("data/tests/snake-compress");
+            let mut db = SnakeDb::new("tests/snake-compress").unwrap();
+            db.set_compress(true);
+
+            let text = "some detail text that repeats itself. ".repeat(100);
+            let len = text.len() as u64;
+            let mut head = SnakeHead::default();
+            db.alloc(len + 100, &mut head).expect("alloc");
+            let (gene, position) = (head.gene, head.position);
+            db.write(&gene, &mut head, 0, text.as_bytes()).expect("write");
+            assert!(!head.flags.is_compressed());
+
+            db.set_length(&gene, &mut head, len).expect("set length");
+            assert!(head.flags.is_compressed());
+            assert_eq!(head.length, len);
+            assert!(head.stored * 5 < len);
+            // the capacity is kept, the region is only the stored bytes
+            assert_eq!(head.capacity, len + 100);
+            assert_eq!(frees(&mut db), [(position, len + 100)]);
+
+            let mut buf = vec![0u8; 100];
+            db.read(&gene, &mut head, 1000, &mut buf).expect("read");
+            assert_eq!(buf, text.as_bytes()[1000..1100]);
+
+            // a write stores it uncompressed again
+            db.write(&gene, &mut head, 0, b"S").expect("write");
+            assert!(!head.flags.is_compressed());
+            assert_eq!(head.stored, len);
+            assert!(head.capacity >= len);
+            db.read(&gene, &mut head, 0, &mut buf).expect("read");
+            assert_eq!(buf[..5], *b"Some ");
+            assert_eq!(buf[5..], text.as_bytes()[5..100]);
+
+            // content that does not get smaller is kept as it is
+            let mut x = 0x2545f491u32;
+            let noise: Vec<u8> = (0..5000)
+                .map(|_| {
+                    x ^= x << 13;
+                    x ^= x >> 17;
+                    x ^= x << 5;
+                    x as u8
+                })
+                .collect();
+            db.alloc(5000, &mut head).expect("alloc");
+            let size = db.stats().unwrap().size;
+            let gene = head.gene;
+            db.write(&gene, &mut head, 0, &noise).expect("write");
+            db.set_length(&gene, &mut head, 5000).expect("set length");
+            assert!(!head.flags.is_compressed());
+            assert_eq!(db.stats().unwrap().size, size);
+
+            // freeing a compressed snake frees its stored bytes
+            db.alloc(len, &mut head).expect("alloc");
+            let gene = head.gene;
+            db.write(&gene, &mut head, 0, text.as_bytes()).expect("write");
+            db.set_length(&gene, &mut head, len).expect("set length");
+            assert!(head.flags.is_compressed());
+            let before = db.stats().unwrap().free_bytes;
+            db.free(&gene).expect("free");
+            let after = db.stats().unwrap().free_bytes;
+            assert_eq!(after, before + head.stored);
+        }
+
+        thread_local! {
+            static CLIENT_DB: std::cell::RefCell<Option<SnakeDb>> =
+                const { std::cell::RefCell::new(None) };
+        }
+
+        fn client_db<T>(
+            f: impl FnOnce(&mut SnakeDb) -> Result<T, ShahError>,
+        ) -> Result<T, ClientError<ExampleError>> {
+            CLIENT_DB.with(|db| Ok(f(db.borrow_mut().as_mut().unwrap())?))
+        }
+
+        const LOCAL: SnakeClient<ExampleError> = SnakeClient {
+            head: |_, gene| {
+                client_db(|db| {
+                    let mut head = SnakeHead::default();
+                    db.index.get(gene, &mut head)?;
+                    Ok(head)
+                })
+            },
+            read: |_, gene, offset| {
+                client_db(|db| {
+                    let mut out = (SnakeHead::default(), [0u8; BLOCK_SIZE]);
+                    db.read(gene, &mut out.0, *offset, &mut out.1)?;
+                    Ok(out)
+                })
+            },
+            write: |_, gene, offset, buf, len| {
+                client_db(|db| {
+                    let mut head = SnakeHead::default();
+                    let data = &buf[..*len as usize];
+                    db.write(gene, &mut head, *offset, data)?;
+                    Ok(head)
+                })
+            },
+            set_length: |_, gene, len| {
+                client_db(|db| {
+                    let mut head = SnakeHead::default();
+                    db.set_length(gene, &mut head, *len)?;
+                    Ok(head)
+                })
+            },
+        };
+
+        #[test]
+        fn compress_round_trip() {
+            use std::io::{Read, Seek, SeekFrom, Write};
+
+            let _ = std::fs::remove_dir_all("data/tests/snake-compress-io");
+            let mut db = SnakeDb::new("tests/snake-compress-io").unwrap();
+            db.set_compress(true);
+
+            // more than one compressed block
+            let text: Vec<u8> = (0..200_000u32)
+                .flat_map(|i| {
+                    format!("line {} of the detail. ", i % 97).into_bytes()
+                })
+                .take(200_000)
+                .collect();
+            let mut head = SnakeHead::default();
+            db.alloc(text.len() as u64, &mut head).expect("alloc");
+            let gene = head.gene;
+
+            let mut file = db.open(&gene).expect("open");
+            file.write_all(&text).expect("write");
+            file.flush().expect("flush");
+            assert!(file.head().flags.is_compressed());
+            assert!(file.head().stored * 4 < text.len() as u64);
+            assert_eq!(file.head().capacity, text.len() as u64);
+
+            file.rewind().expect("rewind");
+            let mut out = Vec::new();
+            file.read_to_end(&mut out).expect("read");
+            assert_eq!(out, text);
+            let mut tail = [0u8; 10];
+            file.seek(SeekFrom::End(-10)).expect("seek");
+            file.read_exact(&mut tail).expect("read tail");
+            assert_eq!(tail, text[text.len() - 10..]);
+            drop(file);
+
+            CLIENT_DB.with(|c| *c.borrow_mut() = Some(db));
+            let taker =
+                Taker::init("none", "data/tests/snake-compress-io/taker.sock")
+                    .expect("taker");
+
+            let data = LOCAL.read(&taker, &gene).expect("client read");
+            assert_eq!(data, text);
+            let data = LOCAL
+                .read_range(&taker, &gene, 65_000, 1000)
+                .expect("client range");
+            assert_eq!(data, text[65_000..66_000]);
+
+            // a client write expands it and set_length packs it again
+            let head =
+                LOCAL.write_at(&taker, &gene, 10, b"LINE").expect("write");
+            assert!(head.flags.is_compressed());
+            assert_eq!(head.length, text.len() as u64);
+            let data = LOCAL.read_range(&taker, &gene, 0, 20).expect("read");
+            assert_eq!(data[10..14], *b"LINE");
+            assert_eq!(data[14..], text[14..20]);
+            CLIENT_DB.with(|c| c.borrow_mut().take());
+        }
+
+        #[test]
+        fn free_list() {
+            let _ = std::fs::remove_dir_all("data/tests/snake-free-list");
+            let mut db = SnakeDb::new("tests/snake-free-list").unwrap();
+
+            // more frees than the old fixed list could hold
+            let count = 5000u64;
+            let mut head = SnakeHead::default();
+            let mut genes = Vec::new();
+            for _ in 0..count * 2 {
+                db.alloc(100, &mut head).expect("alloc");
+                genes.push(head.gene);
+            }
+            for gene in genes.iter().step_by(2) {
+                db.free(gene).expect("free");
+            }
+            let stats = db.stats().expect("stats");
+            assert_eq!((stats.free, stats.free_bytes), (count, count * 100));
+
+            // kept on disk over a restart
+            drop(db);
+            let mut db = SnakeDb::new("tests/snake-free-list").unwrap();
+            let stats = db.stats().expect("stats");
+            assert_eq!((stats.free, stats.free_bytes), (count, count * 100));
+
+            // a db without the free list file builds it from the index
+            drop(db);
+            std::fs::remove_file("data/tests/snake-free-list/free.snake.shah")
+                .expect("remove");
+            let mut db = SnakeDb::new("tests/snake-free-list").unwrap();
+            assert_eq!(db.stats().expect("stats").free, 0);
+            while db.work().expect("work").0 {}
+            let stats = db.stats().expect("stats");
+            assert_eq!((stats.free, stats.free_bytes), (count, count * 100));
+
+            // the best fit is a freed region and not the end of the file
+            db.alloc(1000, &mut head).expect("alloc big");
+            assert_eq!(head.position + head.capacity, db.stats().unwrap().size);
+            db.alloc(90, &mut head).expect("alloc small");
+            assert!(head.position < stats.size);
+            assert_eq!(db.stats().unwrap().free, count - 1);
+        }
+
+        #[test]
+        fn index_koch() {
+            use shah::db::entity::EntityDb;
+            use shah::db::snake::SnakeHeadV0;
+
+            let _ = std::fs::remove_dir_all("data/tests/snake-koch");
+            let mut old =
+                EntityDb::<SnakeHeadV0>::new("tests/snake-koch/index", 0)
+                    .unwrap();
+            let mut v0 = SnakeHeadV0::default();
+            (v0.capacity, v0.position, v0.length) = (100, 64 * 1024, 5);
+            old.add(&mut v0).expect("add");
+            drop(old);
+
+            // read from the old index before the koch task gets to it
+            let mut db = SnakeDb::new("tests/snake-koch").unwrap();
+            let mut head = SnakeHead::default();
+            db.index.get(&v0.gene, &mut head).expect("get");
+            assert_eq!((head.position, head.capacity), (64 * 1024, 100));
+            assert_eq!((head.length, head.stored, head.refs), (5, 5, 1));
+
+            while db.work().expect("work").0 {}
+            db.write(&v0.gene, &mut head, 0, b"hello").expect("write");
+            let mut buf = [0u8; 5];
+            db.read(&v0.gene, &mut head, 0, &mut buf).expect("read");
+            assert_eq!(&buf, b"hello");
+        }
+
+        #[test]
+        fn defrag() {
+            let _ = std::fs::remove_dir_all("data/tests/snake-defrag");
+            let mut db = SnakeDb::new("tests/snake-defrag").unwrap();
+
+            let mut heads = [SnakeHead::default(); 4];
+            for (i, head) in heads.iter_mut().enumerate() {
+                db.alloc(1000, head).expect("alloc");
+                let gene = head.gene;
+                db.write(&gene, head, 0, &[i as u8 + 1; 1000]).expect("write");
+            }
+
+            // neighbours are merged on free
+            db.free(&heads[0].gene).expect("free");
+            db.free(&heads[1].gene).expect("free");
+            assert_eq!(frees(&mut db), [(heads[0].position, 2000)]);
+
+            db.set_defrag(true);
+            for _ in 0..100 {
+                if !db.work().expect("work").0 {
+                    break;
+                }
+            }
+            assert!(frees(&mut db).is_empty());
+
+            let mut head = SnakeHead::default();
+            let mut buf = [0u8; 1000];
+            for (i, old) in heads[2..].iter().enumerate() {
+                db.read(&old.gene, &mut head, 0, &mut buf).expect("read");
+                assert_eq!(head.position, heads[i].position);
+                assert!(buf.iter().all(|b| *b == i as u8 + 3));
+            }
+
+            let size =
+                std::fs::metadata("data/tests/snake-defrag/data.snake.shah")
+                    .expect("meta")
+                    .len();
+            assert_eq!(size, head.position + head.capacity);
+        }
+
+        #[test]
+        fn defrag_fit() {
+            let _ = std::fs::remove_dir_all("data/tests/snake-defrag-fit");
+            let mut db = SnakeDb::new("tests/snake-defrag-fit").unwrap();
+
+            let mut heads = [SnakeHead::default(); 4];
+            for (i, cap) in [1000, 500, 1000, 400].into_iter().enumerate() {
+                let head = &mut heads[i];
+                db.alloc(cap, head).expect("alloc");
+                let gene = head.gene;
+                let data = vec![i as u8 + 1; cap as usize];
+                db.write(&gene, head, 0, &data).expect("write");
+            }
+
+            // the gap is too small for the snake after it but the last
+            // one fits in it
+            db.free(&heads[1].gene).expect("free");
+            db.set_defrag(true);
+            for _ in 0..100 {
+                if !db.work().expect("work").0 {
+                    break;
+                }
+            }
+            assert_eq!(frees(&mut db), [(heads[1].position + 400, 100)]);
+
+            let mut head = SnakeHead::default();
+            let mut buf = [0u8; 400];
+            db.read(&heads[3].gene, &mut head, 0, &mut buf).expect("read");
+            assert_eq!(head.position, heads[1].position);
+            assert!(buf.iter().all(|b| *b == 4));
+
+            let size = db.stats().expect("stats").size;
+            assert_eq!(size, heads[2].position + heads[2].capacity);
+        }
     }
 }
 
 use crate::models::{ExampleError, State};
-use shah::db::snake::SnakeHead;
+use shah::db::snake::{SnakeClient, SnakeHead};
 use shah::models::Gene;
 use shah::{AsUtf8Str, BLOCK_SIZE, ClientError, ErrorCode, Taker};
 
@@ -63,31 +576,23 @@ mod eapi {
     ) -> Result<(), ErrorCode> {
         Ok(state.detail.free(gene)?)
     }
+
+    pub fn realloc(
+        state: &mut State, (gene, capacity): (&Gene, &u64),
+        (head,): (&mut SnakeHead,),
+    ) -> Result<(), ErrorCode> {
+        Ok(state.detail.realloc(gene, *capacity, head)?)
+    }
 }
 
+const CLIENT: SnakeClient<ExampleError> =
+    SnakeClient { head, read, write, set_length };
+
 pub fn get(
     taker: &Taker, gene: &Gene,
 ) -> Result<String, ClientError<ExampleError>> {
-    let (head, buf) = read(taker, gene, &0)?;
-    let len = head.length.min(head.capacity);
-    let len = if len == 0 { head.capacity } else { len } as usize;
-    // let mut v = Vec::with_capacity(len);
-    // unsafe { v.set_len(len) };
-    let mut v = vec![0u8; len];
-
-    if len > BLOCK_SIZE {
-        v[..BLOCK_SIZE].copy_from_slice(&buf);
-        for i in 1..=(len / BLOCK_SIZE) {
-            let off = i * BLOCK_SIZE;
-            let (_, buf) = read(taker, gene, &(off as u64))?;
-            v[off..(off + BLOCK_SIZE).min(len)]
-                .copy_from_slice(&buf[..(len - off).min(BLOCK_SIZE)])
-        }
-    } else {
-        v.copy_from_slice(&buf[..len]);
-    }
-
-    Ok(v.as_utf8_str().to_string())
+    let data = CLIENT.read(taker, gene)?;
+    Ok(data.as_utf8_str().to_string())
 }
 
 pub fn set(
@@ -95,45 +600,20 @@ pub fn set(
 ) -> Result<Gene, ClientError<ExampleError>> {
     let data = data.as_bytes();
     let len = data.len().min(DETAIL_MAX);
-    let mut snake: Option<SnakeHead> = None;
-    if let Some(old) = gene {
-        let old_head = head(taker, old)?;
-        if old_head.capacity >= len as u64 {
-            snake = Some(old_head);
-        } else {
-            free(taker, old)?;
+    let capacity = (len + DETAIL_BUF).min(DETAIL_MAX) as u64;
+    let snake = match gene {
+        Some(old) => {
+            let old_head = head(taker, old)?;
+            if old_head.capacity >= len as u64 {
+                old_head
+            } else {
+                realloc(taker, old, &capacity)?
+            }
         }
-    }
-    if snake.is_none() {
-        let capacity = (len + DETAIL_BUF).min(DETAIL_MAX) as u64;
-        snake = Some(init(taker, &capacity)?);
-    }
-    let snake = snake.unwrap();
-    for i in 0..=(len / BLOCK_SIZE) {
-        let off = i * BLOCK_SIZE;
-        if len < (off + BLOCK_SIZE) {
-            let mut write_buffer = [0u8; BLOCK_SIZE];
-            let wlen = len - off;
-            write_buffer[0..wlen].copy_from_slice(&data[off..len]);
-            write(
-                taker,
-                &snake.gene,
-                &(off as u64),
-                &write_buffer,
-                &(wlen as u64),
-            )?;
-        } else {
-            write(
-                taker,
-                &snake.gene,
-                &(off as u64),
-                &data[off..off + BLOCK_SIZE].try_into().unwrap(),
-                &(BLOCK_SIZE as u64),
-            )?;
-        }
-    }
+        None => init(taker, &capacity)?,
+    };
 
-    set_length(taker, &snake.gene, &(len as u64))?;
+    CLIENT.write(taker, &snake.gene, &data[..len])?;
 
     Ok(snake.gene)
 }
@@ -1,13 +1,15 @@
 use crate::models::{ExampleError, State};
+use shah::ShahError;
+use shah::db::belt::{BeltClient, BeltDb, BeltWalk};
 use shah::models::{Binary, Gene};
 use shah::{AsUtf8Str, BLOCK_SIZE, ClientError, ErrorCode, OptNotFound, Taker};
-use shah::{ShahError, db::belt::BeltDb};
 
 #[allow(unused_imports)]
 pub use client::*;
 pub use db::{Extra, ExtraRoot};
 
 const EXTRA_DATA: usize = BLOCK_SIZE * 2 - 3;
+const WALK_PAGE: usize = 4;
 
 pub(crate) mod db {
     use shah::db::entity::EntityFlags;
@@ -48,6 +50,434 @@ pub(crate) mod db {
     pub fn init() -> Result<ExtraDb, ShahError> {
         ExtraDb::new("extra", 1, 1)
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::{Extra, ExtraDb, ExtraRoot};
+        use shah::db::belt::cloth::{BeltClothDb, ClothBelt, ClothBuckle};
+        use shah::db::belt::{BeltDb, BeltWalk, ShahBuckle};
+        use shah::db::entity::EntityFlags;
+        use shah::models::{Gene, Worker};
+        use shah::{PAGE_SIZE, ShahError, SystemError};
+        use std::io::Read;
+        use std::ops::RangeBounds;
+
+        fn order(db: &mut ExtraDb, root: &Gene) -> Vec<u16> {
+            let mut buckle = ExtraRoot::default();
+            db.buckle_get(root, &mut buckle).expect("buckle");
+
+            let mut forward = Vec::new();
+            let mut belt = Extra::default();
+            let mut gene = buckle.head;
+            while gene.is_some() {
+                db.belt_get(&gene, &mut belt).expect("belt");
+                assert_eq!(belt.buckle, buckle.gene);
+                forward.push(belt.length);
+                gene = belt.next;
+            }
+
+            let mut backward = Vec::new();
+            let mut gene = buckle.tail;
+            while gene.is_some() {
+                db.belt_get(&gene, &mut belt).expect("belt");
+                backward.push(belt.length);
+                gene = belt.past;
+            }
+            backward.reverse();
+
+            assert_eq!(forward, backward);
+
+            let mut page = [Extra::default(); 2];
+            let mut walked = Vec::new();
+            let mut from = BeltWalk::HEAD;
+            loop {
+                let n = db.walk(root, &from, &mut page).expect("walk");
+                walked.extend(page[..n].iter().map(|b| b.length));
+                if n < page.len() {
+                    assert!(page[n..].iter().all(|b| b.gene.is_none()));
+                    break;
+                }
+                from = BeltWalk::after(page[n - 1].gene);
+            }
+            assert_eq!(forward, walked);
+
+            let mut page = [Extra::default(); PAGE_SIZE];
+            let n = db.walk(root, &BeltWalk::TAIL, &mut page).expect("walk");
+            let walked = page[..n].iter().rev().map(|b| b.length);
+            assert_eq!(forward, walked.collect::<Vec<_>>());
+            assert_eq!(buckle.belt_count, forward.len() as u64);
+            forward
+        }
+
+        #[test]
+        fn belt_links() {
+            let _ = std::fs::remove_dir_all("data/tests/extra");
+            let mut db = ExtraDb::new("tests/extra", 1, 1).unwrap();
+
+            let mut root = ExtraRoot::default();
+            db.buckle_init(&Gene::NONE, &mut root).expect("init");
+
+            let mut genes = Vec::new();
+            for i in [1, 2, 3] {
+                let mut belt = Extra { length: i, ..Default::default() };
+                db.belt_add(&root.gene, &mut belt).expect("add");
+                genes.push(belt.gene);
+            }
+            assert_eq!(order(&mut db, &root.gene), [1, 2, 3]);
+
+            let mut belt = Extra { length: 4, ..Default::default() };
+            db.belt_insert_after(&genes[0], &mut belt).expect("after");
+            assert_eq!(order(&mut db, &root.gene), [1, 4, 2, 3]);
+
+            let mut belt = Extra { length: 5, ..Default::default() };
+            db.belt_insert_after(&genes[2], &mut belt).expect("after");
+            let tail = belt.gene;
+            assert_eq!(order(&mut db, &root.gene), [1, 4, 2, 3, 5]);
+
+            let mut belt = Extra { length: 6, ..Default::default() };
+            db.belt_insert_before(&genes[0], &mut belt).expect("before");
+            assert_eq!(order(&mut db, &root.gene), [6, 1, 4, 2, 3, 5]);
+
+            let mut belt = Extra { length: 7, ..Default::default() };
+            db.belt_insert_before(&genes[2], &mut belt).expect("before");
+            assert_eq!(order(&mut db, &root.gene), [6, 1, 4, 2, 7, 3, 5]);
+
+            db.move_to_head(&genes[1], &mut belt).expect("head");
+            assert_eq!(order(&mut db, &root.gene), [2, 6, 1, 4, 7, 3, 5]);
+
+            db.move_to_head(&tail, &mut belt).expect("head");
+            assert_eq!(order(&mut db, &root.gene), [5, 2, 6, 1, 4, 7, 3]);
+
+            db.move_to_tail(&genes[1], &mut belt).expect("tail");
+            assert_eq!(order(&mut db, &root.gene), [5, 6, 1, 4, 7, 3, 2]);
+
+            db.belt_del(&genes[0], &mut belt).expect("del");
+            assert_eq!(order(&mut db, &root.gene), [5, 6, 4, 7, 3, 2]);
+        }
+
+        #[test]
+        fn buckle_sweep() {
+            let _ = std::fs::remove_dir_all("data/tests/sweep");
+            let mut db = ScoreDb::new("tests/sweep", 1, 1).unwrap();
+
+            let mut root = ShahBuckle::default();
+            db.buckle_init(&Gene::NONE, &mut root).expect("init");
+            let mut belts = [Score::default(); 100];
+            db.belt_add_bulk(&root.gene, &mut belts).expect("add");
+
+            let mut other = ShahBuckle::default();
+            db.buckle_init(&Gene::NONE, &mut other).expect("init");
+            let mut kept = Score::default();
+            db.belt_add(&other.gene, &mut kept).expect("add");
+
+            db.buckle_del(&root.gene).expect("del");
+            assert!(db.buckle_get(&root.gene, &mut other).is_err());
+            assert_eq!(db.sweep_pending(), 1);
+
+            while db.sweep_pending() > 0
+                && db.belt_get(&belts[60].gene, &mut kept).is_ok()
+            {
+                db.work().expect("work");
+            }
+            assert_eq!(db.sweep_pending(), 1);
+            assert!(db.belt_get(&belts[0].gene, &mut kept).is_err());
+            drop(db);
+
+            let mut db = ScoreDb::new("tests/sweep", 1, 1).unwrap();
+            assert_eq!(db.sweep_pending(), 1);
+            assert!(db.belt_get(&belts[99].gene, &mut kept).is_ok());
+            while db.work().expect("work").0 {}
+
+            assert_eq!(db.sweep_pending(), 0);
+            for belt in belts.iter() {
+                assert!(db.belt_get(&belt.gene, &mut kept).is_err());
+            }
+            assert_eq!(db.belt_count().expect("count").alive, 1);
+
+            drop(db);
+            let db = ScoreDb::new("tests/sweep", 1, 1).unwrap();
+            assert_eq!(db.sweep_pending(), 0);
+        }
+
+        #[test]
+        fn blob() {
+            let _ = std::fs::remove_dir_all("data/tests/blob");
+            let mut db = BeltClothDb::<16>::new("tests/blob", 1, 1).unwrap();
+
+            let mut root = ClothBuckle::default();
+            db.buckle_init(&Gene::NONE, &mut root).expect("init");
+            let bg = root.gene;
+
+            let data: Vec<u8> = (0..100u8).collect();
+            db.write(&bg, &data).expect("write");
+            assert_eq!(db.read(&bg).expect("read"), data);
+            assert_eq!(
+                db.read_range(&bg, 10, 20).expect("range"),
+                data[10..30]
+            );
+            assert_eq!(db.read_range(&bg, 90, 100).expect("range"), data[90..]);
+            assert!(db.read_range(&bg, 100, 1).expect("range").is_empty());
+
+            let mut stream = Vec::new();
+            db.reader(&bg).expect("reader").read_to_end(&mut stream).unwrap();
+            assert_eq!(stream, data);
+
+            db.truncate(&bg, 33).expect("truncate");
+            db.buckle_get(&bg, &mut root).expect("buckle");
+            assert_eq!((root.length, root.chunks), (33, 3));
+            assert_eq!(db.read(&bg).expect("read"), data[..33]);
+
+            db.append(&bg, &data[50..]).expect("append");
+            let mut want = data[..33].to_vec();
+            want.extend_from_slice(&data[50..]);
+            assert_eq!(db.read(&bg).expect("read"), want);
+            db.buckle_get(&bg, &mut root).expect("buckle");
+            assert_eq!((root.length, root.chunks), (83, 6));
+
+            db.write(&bg, b"short").expect("write");
+            assert_eq!(db.read(&bg).expect("read"), b"short");
+            assert_eq!(db.belt_count().expect("count").alive, 1);
+
+            db.truncate(&bg, 0).expect("truncate");
+            db.buckle_get(&bg, &mut root).expect("buckle");
+            assert_eq!((root.length, root.chunks), (0, 0));
+            assert!(root.head.is_none() && root.tail.is_none());
+            assert_eq!(db.belt_count().expect("count").alive, 0);
+
+            db.set(&bg, "hello world").expect("set");
+            assert_eq!(db.get(&bg).expect("get"), "hello world");
+
+            // a cloth from before blobs: a shorter set kept the old belts
+            // after the end and only moved `is_end`
+            let mut old = ClothBuckle::default();
+            db.buckle_init(&Gene::NONE, &mut old).expect("init");
+            let og = old.gene;
+            db.write(&og, &data[..40]).expect("write");
+            db.buckle_get(&og, &mut old).expect("buckle");
+            let mut end = ClothBelt::<16>::default();
+            db.belt_get(&old.tail, &mut end).expect("tail");
+            end.gene = old.head;
+            end.data.set("hello");
+            end.length = 5;
+            db.belt_set(&mut end).expect("end");
+            old.length = 5;
+            db.buckle_set(&mut old).expect("buckle set");
+            assert_eq!(db.read(&og).expect("read"), b"hello");
+
+            db.append(&og, &data[..20]).expect("append");
+            let mut want = b"hello".to_vec();
+            want.extend_from_slice(&data[..20]);
+            assert_eq!(db.read(&og).expect("read"), want);
+            db.buckle_get(&og, &mut old).expect("buckle");
+            assert_eq!((old.length, old.chunks), (25, 2));
+
+            // the length is a u32
+            db.buckle_get(&bg, &mut root).expect("buckle");
+            root.length = u32::MAX - 4;
+            db.buckle_set(&mut root).expect("buckle set");
+            assert!(matches!(
+                db.append(&bg, &data[..5]).expect_err("append"),
+                ShahError::System(SystemError::BlobTooBig)
+            ));
+            db.buckle_get(&bg, &mut root).expect("buckle");
+            assert_eq!((root.length, root.chunks), (u32::MAX - 4, 1));
+        }
+
+        #[derive(shah::ShahSchema)]
+        #[shah::model]
+        #[derive(Debug, shah::Entity, shah::Belt)]
+        struct Score {
+            gene: Gene,
+            next: Gene,
+            past: Gene,
+            buckle: Gene,
+            growth: u64,
+            #[belt(order)]
+            score: u64,
+            entity_flags: EntityFlags,
+            _pad: [u8; 7],
+        }
+
+        type ScoreDb = BeltDb<Score>;
+
+        fn scores(
+            db: &mut ScoreDb, root: &Gene,
+            range: impl RangeBounds<u64> + Clone, from: BeltWalk,
+        ) -> Vec<u64> {
+            let mut page = [Score::default(); 2];
+            let mut out = Vec::new();
+            let mut from = from;
+            while !from.done {
+                let n = db
+                    .walk_range(root, &mut from, range.clone(), &mut page)
+                    .expect("walk");
+                out.extend(page[..n].iter().map(|s| s.score));
+            }
+            out
+        }
+
+        #[test]
+        fn sorted_belts() {
+            let _ = std::fs::remove_dir_all("data/tests/score");
+            let mut db = ScoreDb::new("tests/score", 1, 1).unwrap();
+
+            let mut root = ShahBuckle::default();
+            db.buckle_init(&Gene::NONE, &mut root).expect("init");
+
+            let mut genes = Vec::new();
+            for score in [5, 1, 3, 3, 9, 0] {
+                let mut belt = Score { score, ..Default::default() };
+                db.belt_add(&root.gene, &mut belt).expect("add");
+                genes.push(belt.gene);
+            }
+
+            let all = scores(&mut db, &root.gene, .., BeltWalk::HEAD);
+            assert_eq!(all, [0, 1, 3, 3, 5, 9]);
+            let top = scores(&mut db, &root.gene, .., BeltWalk::TAIL);
+            assert_eq!(top, [9, 5, 3, 3, 1, 0]);
+
+            let mid = scores(&mut db, &root.gene, 2..=5, BeltWalk::HEAD);
+            assert_eq!(mid, [3, 3, 5]);
+            let mid = scores(&mut db, &root.gene, 1..5, BeltWalk::TAIL);
+            assert_eq!(mid, [3, 3, 1]);
+            assert!(
+                scores(&mut db, &root.gene, 6..9, BeltWalk::HEAD).is_empty()
+            );
+
+            // equal keys keep the insertion order
+            let mut page = [Score::default(); 2];
+            let mut from = BeltWalk::HEAD;
+            db.walk_range(&root.gene, &mut from, 3..=3, &mut page)
+                .expect("walk");
+            assert_eq!([page[0].gene, page[1].gene], [genes[2], genes[3]]);
+            assert_eq!((from.anchor, from.done), (genes[3], false));
+            let n = db
+                .walk_range(&root.gene, &mut from, 3..=3, &mut page)
+                .expect("walk");
+            assert_eq!((n, from.done), (0, true));
+
+            // a search near the place, equal keys still go last
+            let mut belt = Score { score: 3, ..Default::default() };
+            db.belt_add_near(&root.gene, &genes[0], &mut belt).expect("add");
+            let all = scores(&mut db, &root.gene, .., BeltWalk::HEAD);
+            assert_eq!(all, [0, 1, 3, 3, 3, 5, 9]);
+            let mut page = [Score::default(); 3];
+            let mut from = BeltWalk::HEAD;
+            db.walk_range(&root.gene, &mut from, 3..=3, &mut page)
+                .expect("walk");
+            assert_eq!(page[2].gene, belt.gene);
+            let gene = belt.gene;
+            db.belt_del(&gene, &mut belt).expect("del");
+
+            let mut belt = Score::default();
+            db.belt_get(&genes[1], &mut belt).expect("get");
+            belt.score = 7;
+            db.belt_set(&mut belt).expect("set");
+            let all = scores(&mut db, &root.gene, .., BeltWalk::HEAD);
+            assert_eq!(all, [0, 3, 3, 5, 7, 9]);
+            let top = scores(&mut db, &root.gene, .., BeltWalk::TAIL);
+            assert_eq!(top, [9, 7, 5, 3, 3, 0]);
+
+            let gene = root.gene;
+            db.buckle_get(&gene, &mut root).expect("buckle");
+            assert_eq!(root.belt_count, 6);
+
+            let mut belt = Score { score: 4, ..Default::default() };
+            let err = db.belt_insert_after(&genes[0], &mut belt).expect_err("");
+            assert!(matches!(
+                err,
+                ShahError::System(SystemError::BeltIsSorted)
+            ));
+            let err = db.move_to_head(&genes[0], &mut belt).expect_err("");
+            assert!(matches!(
+                err,
+                ShahError::System(SystemError::BeltIsSorted)
+            ));
+        }
+
+        #[test]
+        fn sorted_lanes() {
+            let _ = std::fs::remove_dir_all("data/tests/score-lanes");
+            let mut db = ScoreDb::new("tests/score-lanes", 1, 1).unwrap();
+
+            let mut root = ShahBuckle::default();
+            db.buckle_init(&Gene::NONE, &mut root).expect("init");
+            let mut other = ShahBuckle::default();
+            db.buckle_init(&Gene::NONE, &mut other).expect("init");
+
+            let mut seed = 0x9e37_79b9_7f4a_7c15u64;
+            let mut rand = move || {
+                seed ^= seed << 13;
+                seed ^= seed >> 7;
+                seed ^= seed << 17;
+                seed
+            };
+
+            let mut model: Vec<(u64, Gene)> = Vec::new();
+            for _ in 0..2000 {
+                let mut belt =
+                    Score { score: rand() % 500, ..Default::default() };
+                db.belt_add(&root.gene, &mut belt).expect("add");
+                model.push((belt.score, belt.gene));
+            }
+            for i in 0..600 {
+                let at = (rand() % model.len() as u64) as usize;
+                let (_, gene) = model.swap_remove(at);
+                let mut belt = Score::default();
+                match i % 3 {
+                    0 => db.belt_del(&gene, &mut belt).expect("del"),
+                    1 => {
+                        db.belt_get(&gene, &mut belt).expect("get");
+                        belt.score = rand() % 500;
+                        db.belt_set(&mut belt).expect("set");
+                        model.push((belt.score, gene));
+                    }
+                    _ => {
+                        db.change_buckle(&gene, &other.gene, &mut belt)
+                            .expect("change");
+                    }
+                }
+            }
+
+            let mut sorted: Vec<u64> = model.iter().map(|(s, _)| *s).collect();
+            sorted.sort();
+            let all = scores(&mut db, &root.gene, .., BeltWalk::HEAD);
+            assert_eq!(all, sorted);
+            let top = scores(&mut db, &root.gene, .., BeltWalk::TAIL);
+            assert!(top.iter().rev().eq(sorted.iter()));
+
+            let mid: Vec<u64> = sorted
+                .iter()
+                .copied()
+                .filter(|s| (100..300).contains(s))
+                .collect();
+            assert_eq!(
+                scores(&mut db, &root.gene, 100..300, BeltWalk::HEAD),
+                mid
+            );
+            let top = scores(&mut db, &root.gene, 100..300, BeltWalk::TAIL);
+            assert!(top.iter().rev().eq(mid.iter()));
+            assert!(
+                scores(&mut db, &root.gene, 500.., BeltWalk::HEAD).is_empty()
+            );
+            assert!(
+                scores(&mut db, &root.gene, ..0, BeltWalk::TAIL).is_empty()
+            );
+
+            // a walk from the head goes straight to the range
+            let mut page = [Score::default(); 4];
+            let mut from = BeltWalk::HEAD;
+            let n = db
+                .walk_range(&root.gene, &mut from, 499..=499, &mut page)
+                .expect("walk");
+            let last = sorted.iter().filter(|s| **s == 499).count().min(4);
+            assert_eq!(n, last);
+
+            let moved = scores(&mut db, &other.gene, .., BeltWalk::HEAD);
+            assert_eq!(moved.len(), 200);
+            assert!(moved.is_sorted());
+        }
+    }
 }
 
 #[shah::api(scope = 3, error = crate::models::ExampleError)]
@@ -106,6 +536,14 @@ mod eapi {
         state.extra.belt_del(extra_gene, res)?;
         Ok(())
     }
+
+    pub(crate) fn walk(
+        state: &mut State, (buckle_gene, from): (&Gene, &BeltWalk),
+        (page,): (&mut [Extra; WALK_PAGE],),
+    ) -> Result<usize, ErrorCode> {
+        let count = state.extra.walk(buckle_gene, from, page)?;
+        Ok(count * <Extra as shah::models::Binary>::S)
+    }
 }
 
 #[allow(dead_code)]
@@ -115,17 +553,11 @@ pub fn get_all(
     let buckle = root_get(taker, buckle_gene)?;
     let mut data = Vec::with_capacity(buckle.belt_count as usize * EXTRA_DATA);
 
-    let mut gene = buckle.head;
-    loop {
-        if gene.is_none() {
-            break;
-        }
-
-        let Some(extra) = get(taker, &gene).onf()? else { break };
+    let client = BeltClient::<Extra, ExampleError, WALK_PAGE> { walk };
+    client.walk(taker, buckle_gene, BeltWalk::HEAD, |extra| {
         let len = (extra.length as usize).min(extra.data.len());
         data.extend_from_slice(&extra.data[..len]);
-        gene = extra.next;
-    }
+    })?;
 
     Ok(data[..].as_utf8_str_null_terminated().to_string())
 }
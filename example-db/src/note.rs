@@ -24,4 +24,525 @@ pub(crate) mod db {
     pub(crate) fn init() -> Result<NoteDb, ShahError> {
         NoteDb::new("note", 1, 1, 1)
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use shah::db::entity::Entity as _;
+        use shah::db::pond::{
+            PondCursor, PondRemap, ShahOrigin, ShahPond, ShahSortedPond,
+        };
+        use shah::models::{GeneId, Worker};
+        use shah::{DbError, PAGE_SIZE, SystemError};
+
+        #[shah::model]
+        #[derive(Debug, Entity, Duck, ShahSchema)]
+        struct Post {
+            gene: Gene,
+            pond: Gene,
+            growth: u64,
+            #[duck(order)]
+            time: u64,
+            entity_flags: EntityFlags,
+            _pad: [u8; 7],
+        }
+
+        type FeedDb = PondDb<Post, ShahSortedPond>;
+
+        fn add(db: &mut FeedDb, origin: &Gene, time: u64) -> Post {
+            let mut post = Post { time, ..Default::default() };
+            db.add(origin, &mut post).expect("add");
+            post
+        }
+
+        fn times(
+            db: &mut FeedDb, origin: &Gene, range: (u64, u64),
+        ) -> Vec<u64> {
+            let mut out = [Post::default(); PAGE_SIZE * 2];
+            let mut cursor = Gene::NONE;
+            let mut all = Vec::new();
+            loop {
+                let n = db
+                    .range(origin, range.0..=range.1, &mut cursor, &mut out)
+                    .expect("range");
+                all.extend(out[..n].iter().map(|p| p.time));
+                if cursor.is_none() {
+                    break;
+                }
+            }
+            assert!(all.is_sorted());
+            all
+        }
+
+        fn items(db: &mut FeedDb, origin: &Gene, from: PondCursor) -> Vec<u64> {
+            let mut out = [Post::default(); PAGE_SIZE];
+            let mut cursor = from;
+            let mut all = Vec::new();
+            while !cursor.done {
+                let n = db
+                    .origin_items(origin, &mut cursor, &mut out)
+                    .expect("origin items");
+                assert!(out[n..].iter().all(|p| p.gene.is_none()));
+                all.extend(out[..n].iter().map(|p| p.time));
+            }
+            all
+        }
+
+        #[test]
+        fn origin_items() {
+            let _ = std::fs::remove_dir_all("data/tests/feed-items");
+            let mut db = FeedDb::new("tests/feed-items", 1, 1, 1).unwrap();
+
+            let mut origin = ShahOrigin::default();
+            db.origin_init(&Gene::NONE, &mut origin).expect("init");
+            let og = origin.gene;
+            assert!(items(&mut db, &og, PondCursor::HEAD).is_empty());
+
+            let mut posts = Vec::new();
+            for time in 0..70 {
+                posts.push(add(&mut db, &og, time));
+            }
+            for post in posts.iter().filter(|p| p.time % 3 == 0) {
+                let mut out = Post::default();
+                db.del(&post.gene, &mut out).expect("del");
+            }
+
+            let want: Vec<u64> = (0..70).filter(|t| t % 3 != 0).collect();
+            assert_eq!(items(&mut db, &og, PondCursor::HEAD), want);
+
+            let rev: Vec<u64> = want.iter().rev().copied().collect();
+            assert_eq!(items(&mut db, &og, PondCursor::TAIL), rev);
+        }
+
+        #[test]
+        fn change_origin() {
+            let _ = std::fs::remove_dir_all("data/tests/note-change");
+            let mut db = NoteDb::new("tests/note-change", 1, 1, 1).unwrap();
+
+            let mut alice = ShahOrigin::default();
+            let mut bob = ShahOrigin::default();
+            db.origin_init(&Gene::NONE, &mut alice).expect("alice");
+            db.origin_init(&Gene::NONE, &mut bob).expect("bob");
+
+            let mut note = Note::default();
+            note.note.set("hello");
+            db.add(&alice.gene, &mut note).expect("add");
+
+            let mut moved = Note::default();
+            db.change_origin(&note.gene, &bob.gene, &mut moved)
+                .expect("change origin");
+            assert_ne!(moved.gene, note.gene);
+            assert_eq!(moved.note.as_str(), "hello");
+
+            let mut out = Note::default();
+            assert!(db.get(&note.gene, &mut out).is_err());
+            db.get(&moved.gene, &mut out).expect("moved");
+
+            let mut pond = ShahPond::default();
+            db.pond_get(&moved.pond, &mut pond).expect("pond");
+            assert_eq!((pond.origin, pond.alive), (bob.gene, 1));
+
+            let (ag, bg) = (alice.gene, bob.gene);
+            db.origin_get(&ag, &mut alice).expect("alice");
+            db.origin_get(&bg, &mut bob).expect("bob");
+            assert_eq!((alice.item_count, alice.pond_count), (0, 0));
+            assert_eq!((bob.item_count, bob.pond_count), (1, 1));
+
+            // moving to the same origin keeps the duck as is
+            let gene = moved.gene;
+            db.change_origin(&gene, &bob.gene, &mut moved).expect("same");
+            assert_eq!(moved.gene, gene);
+        }
+
+        #[test]
+        fn stack_size() {
+            type SmallDb = PondDb<
+                Note,
+                ShahPond,
+                ShahOrigin,
+                Note,
+                ShahPond,
+                ShahOrigin,
+                (),
+                (),
+                (),
+                8,
+            >;
+
+            let _ = std::fs::remove_dir_all("data/tests/note-small");
+            let mut db = SmallDb::new("tests/note-small", 1, 1, 1).unwrap();
+
+            let mut origin = ShahOrigin::default();
+            db.origin_init(&Gene::NONE, &mut origin).expect("init");
+            let og = origin.gene;
+
+            let mut notes = Vec::new();
+            for _ in 0..20 {
+                let mut note = Note::default();
+                db.add(&og, &mut note).expect("add");
+                notes.push(note);
+            }
+            db.origin_get(&og, &mut origin).expect("origin");
+            assert_eq!(origin.pond_count, 3);
+
+            let mut pond = ShahPond::default();
+            pond.gene = notes[9].pond;
+            let mut stack = [Note::default(); 8];
+            db.pond_list(&mut pond, &mut stack).expect("pond list");
+            assert_eq!(stack[0].gene, notes[8].gene);
+            assert_eq!(stack[7].gene, notes[15].gene);
+
+            let mut out = Note::default();
+            for note in notes.iter() {
+                db.get(&note.gene, &mut out).expect("get");
+            }
+            drop(db);
+
+            let err = NoteDb::new("tests/note-small", 1, 1, 1)
+                .expect_err("stack size changed");
+            assert!(matches!(err, ShahError::Db(DbError::InvalidDbMeta)));
+
+            // more than a u8 of ducks in a pond
+            type BigDb = PondDb<
+                Note,
+                ShahPond,
+                ShahOrigin,
+                Note,
+                ShahPond,
+                ShahOrigin,
+                (),
+                (),
+                (),
+                300,
+            >;
+
+            let _ = std::fs::remove_dir_all("data/tests/note-big");
+            let mut db = BigDb::new("tests/note-big", 1, 1, 1).unwrap();
+            let mut origin = ShahOrigin::default();
+            db.origin_init(&Gene::NONE, &mut origin).expect("init");
+            let mut notes = Vec::new();
+            for _ in 0..301 {
+                let mut note = Note::default();
+                db.add(&origin.gene, &mut note).expect("add");
+                notes.push(note);
+            }
+            let mut pond = ShahPond::default();
+            db.pond_get(&notes[0].pond, &mut pond).expect("pond");
+            assert_eq!((pond.alive, pond.empty), (300, 0));
+            assert_ne!(notes[300].pond, pond.gene);
+        }
+
+        #[test]
+        fn free_ponds() {
+            let _ = std::fs::remove_dir_all("data/tests/note-free");
+            let mut db = NoteDb::new("tests/note-free", 1, 1, 1).unwrap();
+
+            let mut origin = ShahOrigin::default();
+            db.origin_init(&Gene::NONE, &mut origin).expect("init");
+
+            let mut notes = Vec::new();
+            for _ in 0..PAGE_SIZE * 2 {
+                let mut note = Note::default();
+                db.add(&origin.gene, &mut note).expect("add");
+                notes.push(note);
+            }
+            let mut out = Note::default();
+            for note in notes[PAGE_SIZE..].iter() {
+                db.del(&note.gene, &mut out).expect("del");
+            }
+            let freed = notes[PAGE_SIZE].pond;
+            drop(db);
+
+            let mut db = NoteDb::new("tests/note-free", 1, 1, 1).unwrap();
+            for _ in 0..1000 {
+                if !db.work().expect("work").0 {
+                    break;
+                }
+            }
+
+            let mut other = ShahOrigin::default();
+            db.origin_init(&Gene::NONE, &mut other).expect("other");
+            let mut note = Note::default();
+            db.add(&other.gene, &mut note).expect("add");
+            assert_eq!(note.pond, freed);
+        }
+
+        #[test]
+        fn origin_sweep() {
+            let _ = std::fs::remove_dir_all("data/tests/note-sweep");
+            let mut db = NoteDb::new("tests/note-sweep", 1, 1, 1).unwrap();
+
+            let mut origin = ShahOrigin::default();
+            let mut other = ShahOrigin::default();
+            db.origin_init(&Gene::NONE, &mut origin).expect("init");
+            db.origin_init(&Gene::NONE, &mut other).expect("other");
+            let og = origin.gene;
+            for _ in 0..PAGE_SIZE * 8 {
+                let mut note = Note::default();
+                db.add(&og, &mut note).expect("add");
+            }
+
+            db.origin_del(&og).expect("del");
+            db.origin_get(&og, &mut origin).expect("dying");
+            assert!(origin.entity_flags().is_dying());
+            assert_eq!(db.sweep_pending(), 1);
+            let mut note = Note::default();
+            let err = db.add(&og, &mut note).expect_err("dying");
+            assert!(matches!(err, ShahError::System(SystemError::OriginDying)));
+
+            // the first pond is freed but the crash loses the progress
+            let mut pond = ShahPond::default();
+            db.pond_get(&origin.head, &mut pond).expect("head");
+            db.pond_free(&mut pond).expect("free");
+            drop(db);
+
+            let mut db = NoteDb::new("tests/note-sweep", 1, 1, 1).unwrap();
+            assert_eq!(db.sweep_pending(), 1);
+            let mut note = Note::default();
+            db.add(&other.gene, &mut note).expect("add");
+            for _ in 0..1000 {
+                if !db.work().expect("work").0 {
+                    break;
+                }
+            }
+
+            assert_eq!(db.sweep_pending(), 0);
+            let err = db.origin_get(&og, &mut origin).expect_err("deleted");
+            assert!(matches!(err, ShahError::NotFound(_)));
+            assert_eq!(db.count().expect("count").alive, GeneId(1));
+            let mut pond = ShahPond::default();
+            db.pond_get(&note.pond, &mut pond).expect("pond");
+            assert_eq!((pond.origin, pond.alive), (other.gene, 1));
+        }
+
+        #[test]
+        fn compaction() {
+            let _ = std::fs::remove_dir_all("data/tests/note-compact");
+            let mut db = NoteDb::new("tests/note-compact", 1, 1, 1).unwrap();
+            db.set_compact(4);
+
+            let mut origin = ShahOrigin::default();
+            db.origin_init(&Gene::NONE, &mut origin).expect("init");
+            let og = origin.gene;
+
+            let mut notes = Vec::new();
+            for i in 0..PAGE_SIZE * 3 {
+                let mut note = Note::default();
+                note.note.set(&format!("note {i}"));
+                db.add(&og, &mut note).expect("add");
+                notes.push(note);
+            }
+
+            // the second pond keeps 2 ducks and the third has 3 free slots
+            let mut out = Note::default();
+            let kept = [notes[40], notes[50]];
+            for note in notes[PAGE_SIZE..PAGE_SIZE * 2].iter() {
+                if kept.iter().all(|k| k.gene != note.gene) {
+                    db.del(&note.gene, &mut out).expect("del");
+                }
+            }
+            for note in notes[PAGE_SIZE * 2..PAGE_SIZE * 2 + 3].iter() {
+                db.del(&note.gene, &mut out).expect("del");
+            }
+
+            for _ in 0..1000 {
+                if !db.work().expect("work").0 {
+                    break;
+                }
+            }
+
+            db.origin_get(&og, &mut origin).expect("origin");
+            assert_eq!(origin.pond_count, 2);
+            assert_eq!(origin.item_count, 63);
+
+            let mut remaps = [PondRemap::default(); 4];
+            assert_eq!(db.remap_list(0, &mut remaps).expect("remap"), 2);
+            assert_eq!(db.remap_list(1, &mut remaps[2..]).expect("remap"), 1);
+            assert_eq!(remaps[2].seq, 2);
+            for (remap, note) in remaps.iter().zip(kept.iter()) {
+                assert_eq!(remap.old, note.gene);
+                assert!(db.get(&note.gene, &mut out).is_err());
+                db.get(&remap.new, &mut out).expect("moved");
+                assert_eq!(out.note.as_str(), note.note.as_str());
+            }
+
+            let mut cursor = PondCursor::HEAD;
+            let mut page = [Note::default(); PAGE_SIZE];
+            let mut total = 0;
+            while !cursor.done {
+                total += db
+                    .origin_items(&og, &mut cursor, &mut page)
+                    .expect("origin items");
+            }
+            assert_eq!(total, 63);
+            drop(db);
+
+            // the seq goes on after a restart and only the last is kept
+            let mut db = NoteDb::new("tests/note-compact", 1, 1, 1).unwrap();
+            db.set_compact(4);
+            db.set_remap_keep(1);
+            let mut other = ShahOrigin::default();
+            db.origin_init(&Gene::NONE, &mut other).expect("other");
+            let mut notes = Vec::new();
+            for _ in 0..PAGE_SIZE * 2 {
+                let mut note = Note::default();
+                db.add(&other.gene, &mut note).expect("add");
+                notes.push(note);
+            }
+            for note in notes[..1].iter().chain(notes[PAGE_SIZE + 1..].iter()) {
+                db.del(&note.gene, &mut out).expect("del");
+            }
+            for _ in 0..1000 {
+                if !db.work().expect("work").0 {
+                    break;
+                }
+            }
+            assert_eq!(db.remap_list(0, &mut remaps).expect("remap"), 1);
+            assert_eq!(remaps[0].seq, 3);
+            assert_eq!(remaps[0].old, notes[PAGE_SIZE].gene);
+        }
+
+        #[test]
+        fn pond_koch() {
+            use shah::db::entity::{EntityDb, EntityKoch, EntityKochDb};
+            use shah::db::pond::{ShahPond, old};
+
+            let _ = std::fs::remove_dir_all("data/tests/note-koch");
+            let mut origins =
+                EntityDb::<ShahOrigin>::new("tests/note-koch/origin", 1)
+                    .unwrap();
+            let mut ponds =
+                EntityDb::<old::ShahPond>::new("tests/note-koch/index", 1)
+                    .unwrap();
+            let mut items =
+                EntityDb::<Note>::new("tests/note-koch", 1).unwrap();
+
+            let mut origin = ShahOrigin::default();
+            origins.add(&mut origin).expect("origin");
+            let mut pond = old::ShahPond::default();
+            pond.origin = origin.gene;
+            pond.stack = GeneId(1);
+            pond.alive = PAGE_SIZE as u8;
+            ponds.add(&mut pond).expect("pond");
+            origin.head = pond.gene;
+            origin.tail = pond.gene;
+            origin.pond_count = 1;
+            origin.item_count = PAGE_SIZE as u64;
+            origins.set(&mut origin).expect("origin set");
+            for i in 0..PAGE_SIZE {
+                let mut note = Note { pond: pond.gene, ..Default::default() };
+                note.note.set(&format!("note {i}"));
+                items.add(&mut note).expect("add");
+            }
+            drop((origins, ponds, items));
+
+            let mut db = PondDb::<
+                Note,
+                ShahPond,
+                ShahOrigin,
+                Note,
+                old::ShahPond,
+            >::new("tests/note-koch", 1, 2, 1)
+            .unwrap();
+            let koch = EntityKoch::new(
+                EntityKochDb::new("tests/note-koch/index", 1),
+                (),
+            );
+            db.set_pond_koch(koch).expect("koch");
+            for _ in 0..1000 {
+                if !db.work().expect("work").0 {
+                    break;
+                }
+            }
+
+            let mut new = ShahPond::default();
+            db.pond_get(&pond.gene, &mut new).expect("pond");
+            assert_eq!((new.alive, new.empty), (PAGE_SIZE as u16, 0));
+            assert_eq!(new.stack, GeneId(1));
+
+            let mut note = Note::default();
+            db.add(&origin.gene, &mut note).expect("add");
+            assert_ne!(note.pond, pond.gene);
+            let og = origin.gene;
+            db.origin_get(&og, &mut origin).expect("origin");
+            assert_eq!((origin.pond_count, origin.item_count), (2, 33));
+        }
+
+        #[test]
+        fn sorted_ponds() {
+            let _ = std::fs::remove_dir_all("data/tests/feed");
+            let mut db = FeedDb::new("tests/feed", 1, 1, 1).unwrap();
+
+            let mut origin = ShahOrigin::default();
+            db.origin_init(&Gene::NONE, &mut origin).expect("init");
+            let og = origin.gene;
+
+            let mut posts = Vec::new();
+            for time in 10..110 {
+                posts.push(add(&mut db, &og, time));
+            }
+            db.origin_get(&og, &mut origin).expect("origin");
+            assert_eq!(origin.pond_count, 4);
+
+            assert_eq!(
+                times(&mut db, &og, (20, 29)),
+                (20..30).collect::<Vec<_>>()
+            );
+            assert_eq!(times(&mut db, &og, (0, 200)).len(), 100);
+            assert!(times(&mut db, &og, (200, 300)).is_empty());
+
+            // the first pond is full and 15 falls inside it, so its upper
+            // half moves to a new pond after it
+            add(&mut db, &og, 15);
+            let mut remaps = [PondRemap::default(); PAGE_SIZE];
+            let moved = db.remap_list(0, &mut remaps).expect("remap");
+            assert!(moved > 0);
+            for remap in remaps[..moved].iter() {
+                let post = posts.iter_mut().find(|p| p.gene == remap.old);
+                post.expect("moved post").gene = remap.new;
+            }
+            // smaller than every pond, the head has room after the split
+            add(&mut db, &og, 1);
+            db.origin_get(&og, &mut origin).expect("origin");
+            assert_eq!(origin.pond_count, 5);
+
+            let mut pond = ShahSortedPond::default();
+            db.pond_get(&origin.head, &mut pond).expect("head");
+            assert_eq!(pond.min, 1);
+
+            let mut gene = origin.head;
+            let mut last = 0;
+            while gene.is_some() {
+                db.pond_get(&gene, &mut pond).expect("pond");
+                assert!(last <= pond.min && pond.min <= pond.max);
+                last = pond.max;
+                gene = pond.next;
+            }
+
+            let mut want: Vec<u64> = (10..17).collect();
+            want.insert(6, 15);
+            assert_eq!(times(&mut db, &og, (0, 16)), [vec![1], want].concat());
+            assert_eq!(times(&mut db, &og, (0, 200)).len(), 102);
+
+            // the order is fixed once added
+            let mut post = posts[3];
+            post.time = 500;
+            db.set(&mut post).expect("set");
+            assert_eq!(post.time, 13);
+
+            // deleting the whole first half keeps the rest in range
+            for post in posts[..50].iter() {
+                let mut out = Post::default();
+                db.del(&post.gene, &mut out).expect("del");
+            }
+            assert_eq!(times(&mut db, &og, (0, 200)).len(), 52);
+            assert_eq!(times(&mut db, &og, (60, 61)), [60, 61]);
+
+            let bad = PondDb::<Post>::new("tests/feed-bad", 1, 1, 1);
+            assert!(matches!(
+                bad.expect_err("unsorted pond"),
+                ShahError::System(SystemError::PondNotSorted)
+            ));
+        }
+    }
 }
@@ -1,8 +1,8 @@
 use crate::models::ExampleError;
 use crate::models::State;
-use shah::db::entity::EntityDb;
+use shah::db::entity::{EntityDb, EntityPatch};
 use shah::models::{Gene, GeneId};
-use shah::{Entity, ShahError, ShahSchema};
+use shah::{AsUtf8Str, Entity, ShahError, ShahSchema};
 use shah::{ErrorCode, PAGE_SIZE};
 
 pub use db::User;
@@ -80,6 +80,78 @@ pub(crate) mod db {
     pub(crate) fn init() -> Result<UserDb, ShahError> {
         UserDb::new("user", 1)
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use shah::models::{GeneRecycle, Worker};
+        use std::time::Duration;
+
+        #[test]
+        fn clean_restart() {
+            let _ = std::fs::remove_dir_all("data/tests/user-clean");
+            let mut db = UserDb::new("tests/user-clean", 1).unwrap();
+            let mut genes = Vec::new();
+            for _ in 0..3 {
+                let mut user = User::default();
+                db.add(&mut user).expect("add");
+                genes.push(user.gene);
+            }
+            let mut user = User::default();
+            db.del(&genes[1], &mut user).expect("del");
+
+            // counters and dead ids are there without a setup scan
+            drop(db);
+            let mut db = UserDb::new("tests/user-clean", 1).unwrap();
+            assert_eq!(db.live(), GeneId(2));
+            assert_eq!(db.count().unwrap().dead, GeneId(1));
+            let mut user = User::default();
+            db.add(&mut user).expect("add");
+            assert_eq!(user.gene.id, genes[1].id);
+            assert_eq!(db.live(), GeneId(3));
+        }
+
+        #[test]
+        fn quarantine_restart() {
+            let _ = std::fs::remove_dir_all("data/tests/user-quarantine");
+            let rotate = GeneRecycle::Rotate(Duration::from_millis(300));
+            let mut db = UserDb::new("tests/user-quarantine", 1).unwrap();
+            db.set_gene_recycle(rotate);
+
+            let mut user = User::default();
+            db.add(&mut user).expect("add");
+            let id = user.gene.id;
+            while !user.gene.exhausted() {
+                let gene = user.gene;
+                db.del(&gene, &mut user).expect("del");
+                user.gene = Gene::default();
+                db.add(&mut user).expect("add");
+                assert_eq!(user.gene.id, id);
+            }
+            let gene = user.gene;
+            db.del(&gene, &mut user).expect("del");
+
+            // the quarantine is kept over a clean restart
+            drop(db);
+            let mut db = UserDb::new("tests/user-quarantine", 1).unwrap();
+            db.set_gene_recycle(rotate);
+            while db.work().expect("work").0 {}
+            let mut other = User::default();
+            db.add(&mut other).expect("add");
+            assert_ne!(other.gene.id, id);
+
+            // and keeps running while the db is closed
+            drop(db);
+            std::thread::sleep(Duration::from_millis(300));
+            let mut db = UserDb::new("tests/user-quarantine", 1).unwrap();
+            db.set_gene_recycle(rotate);
+            while db.work().expect("work").0 {}
+            let mut user = User::default();
+            db.add(&mut user).expect("add");
+            assert_eq!(user.gene.id, id);
+            assert_eq!(db.count().unwrap().exhausted, 0);
+        }
+    }
 }
 
 #[shah::api(scope = 0, error = crate::models::ExampleError)]
@@ -116,4 +188,21 @@ mod uapi {
         let count = state.users.list(*page, users)?;
         Ok(count * <User as shah::models::Binary>::S)
     }
+
+    #[patch(users)]
+    pub(crate) fn user_patch(
+        state: &mut State, (patch,): (&EntityPatch,), (user,): (&mut User,),
+    ) -> Result<(), ErrorCode> {
+    }
+
+    pub(crate) fn user_scan(
+        state: &mut State, (cursor, expr): (&GeneId, &[u8; 256]),
+        (next, users): (&mut GeneId, &mut [User; PAGE_SIZE]),
+    ) -> Result<usize, ErrorCode> {
+        *next = *cursor;
+        let expr = expr.as_utf8_str_null_terminated();
+        let count = state.users.scan_where(expr, next, users)?;
+        Ok(<GeneId as shah::models::Binary>::S
+            + count * <User as shah::models::Binary>::S)
+    }
 }
@@ -65,7 +65,8 @@ pub(crate) mod db {
     #[cfg(test)]
     mod tests {
         use shah::{
-            ShahError,
+            NotFound, ShahError,
+            db::trie::TrieCursor,
             models::{Gene, GeneId},
         };
 
@@ -106,5 +107,87 @@ pub(crate) mod db {
                 assert_eq!(db.get(&key).expect("get"), b);
             }
         }
+
+        #[test]
+        fn username_del() {
+            let _ = std::fs::remove_file("data/tst.username-del.shah");
+            let mut db =
+                UsernameDb::new("tst.username-del", UsernameAbc).unwrap();
+            let gene = Gene { id: GeneId(7), ..Default::default() };
+
+            let long = db.key("dr007cc").unwrap();
+            let short = db.key("dr0").unwrap();
+            db.set(&long, gene).expect("set");
+            db.set(&short, gene).expect("set");
+            let size = db.file_size().unwrap();
+
+            // a zero value is a value
+            let zero = db.key("dr").unwrap();
+            assert_eq!(db.set(&zero, Gene::default()).expect("set"), None);
+            assert_eq!(db.get(&zero).expect("get"), Gene::default());
+            assert_eq!(db.del(&zero).expect("del"), Some(Gene::default()));
+            assert!(matches!(
+                db.get(&zero).expect_err("get"),
+                ShahError::NotFound(NotFound::NoTrieValue)
+            ));
+
+            assert_eq!(db.del(&long).expect("del"), Some(gene));
+            assert_eq!(db.del(&long).expect("del"), None);
+            assert!(db.get(&long).is_err());
+            assert_eq!(db.get(&short).expect("get"), gene);
+
+            // the freed nodes are used again
+            db.set(&long, gene).expect("set");
+            assert_eq!(db.file_size().unwrap(), size);
+            assert_eq!(db.get(&long).expect("get"), gene);
+        }
+
+        #[test]
+        fn username_prefix() {
+            let _ = std::fs::remove_file("data/tst.username-prefix.shah");
+            let mut db =
+                UsernameDb::new("tst.username-prefix", UsernameAbc).unwrap();
+
+            let names =
+                ["dr0", "dr007", "dr0a", "dr01", "dr0_x", "dr1", "sadra"];
+            for (i, un) in names.iter().enumerate() {
+                let gene =
+                    Gene { id: GeneId(i as u64 + 1), ..Default::default() };
+                db.set(&db.key(un).unwrap(), gene).expect("set");
+            }
+
+            // sorted by character, not in the order of the abc
+            let prefix = db.key("dr0").unwrap();
+            let mut cursor = TrieCursor::default();
+            let mut found = Vec::new();
+            while !cursor.done {
+                let page = db.prefix(&prefix, 2, &mut cursor).expect("prefix");
+                assert!(page.len() <= 2);
+                found.extend(page.into_iter().map(|(k, v)| (k, v.id.0)));
+            }
+            let expected = [
+                ("dr0", 1),
+                ("dr007", 2),
+                ("dr01", 4),
+                ("dr0_x", 5),
+                ("dr0a", 3),
+            ];
+            assert_eq!(found, expected.map(|(k, v)| (k.to_string(), v)));
+
+            // a deleted last key does not break the cursor
+            let mut cursor = TrieCursor::default();
+            let page = db.prefix(&prefix, 2, &mut cursor).expect("prefix");
+            assert_eq!(page[1].0, "dr007");
+            db.del(&db.key("dr007").unwrap()).expect("del");
+            let page = db.prefix(&prefix, 10, &mut cursor).expect("prefix");
+            let keys: Vec<_> = page.iter().map(|(k, _)| k.as_str()).collect();
+            assert_eq!(keys, ["dr01", "dr0_x", "dr0a"]);
+            assert!(cursor.done);
+
+            let mut cursor = TrieCursor::default();
+            let none = db.key("x").unwrap();
+            assert!(db.prefix(&none, 10, &mut cursor).unwrap().is_empty());
+            assert!(cursor.done);
+        }
     }
 }
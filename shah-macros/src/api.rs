@@ -23,6 +23,12 @@ pub(crate) fn api(
         return err!(item_span, "mod is empty");
     }
 
+    for item in content.iter_mut() {
+        if let syn::Item::Fn(f) = item {
+            patch_route(f)?;
+        }
+    }
+
     let mut s = TokenStream2::new();
 
     let ApiArgs { api_scope, user_error } = parse_args(args)?;
@@ -326,6 +332,56 @@ pub(crate) fn api(
     Ok(s)
 }
 
+/// `#[patch(users)]` on a route with an empty body makes it apply its
+/// `EntityPatch` input to `state.users` and reply with the patched item.
+/// `fn user_patch(state: &mut State, (patch,): (&EntityPatch,),
+/// (user,): (&mut User,)) -> Result<(), ErrorCode> {}`
+fn patch_route(f: &mut syn::ItemFn) -> syn::Result<()> {
+    let Some(idx) = f.attrs.iter().position(|a| a.path().is_ident("patch"))
+    else {
+        return Ok(());
+    };
+    let db: TokenStream2 = f.attrs.remove(idx).parse_args()?;
+
+    if !f.block.stmts.is_empty() {
+        return err!(
+            f.block.span(),
+            "the body of a #[patch] route is generated, leave it empty"
+        );
+    }
+
+    let mut idents = Vec::<syn::Ident>::with_capacity(3);
+    for arg in f.sig.inputs.iter() {
+        let syn::FnArg::Typed(pt) = arg else {
+            return err!(arg.span(), "invalid fn arg :/");
+        };
+        let pat = match &(*pt.pat) {
+            syn::Pat::Tuple(t) if t.elems.len() == 1 => &t.elems[0],
+            p => p,
+        };
+        let syn::Pat::Ident(pi) = pat else {
+            return err!(
+                pat.span(),
+                "a #[patch] route looks like ",
+                "fn(state: &mut S, (patch,): (&EntityPatch,), ",
+                "(item,): (&mut Item,))"
+            );
+        };
+        idents.push(pi.ident.clone());
+    }
+
+    let [state, patch, item] = &idents[..] else {
+        return err!(f.sig.span(), "api functions requires 3 arguments");
+    };
+
+    *f.block = syn::parse_quote!({
+        #state.#db.patch(#patch, #item)?;
+        Ok(())
+    });
+
+    Ok(())
+}
+
 struct ApiArgs {
     user_error: syn::Path,
     api_scope: syn::LitInt,
@@ -33,6 +33,7 @@ pub(crate) fn flags(
     let mut apply = TokenStream2::new();
     let mut from_main = TokenStream2::new();
     let mut key_val = TokenStream2::new();
+    let mut schema_fields = TokenStream2::new();
 
     let key_val_len = item.fields.len();
     let mut do_key_val = true;
@@ -128,6 +129,12 @@ pub(crate) fn flags(
             quote_into! {key_val += (#fname_str, self.#fname),};
         }
 
+        let fname_str = fname.to_string();
+        let (offset, nbits) = (bit_offset as u16, bits as u8);
+        quote_into! {schema_fields +=
+            (String::from(#fname_str), #offset, #nbits),
+        };
+
         bit_offset += bits;
     }
 
@@ -168,21 +175,26 @@ pub(crate) fn flags(
         }
 
         impl #ci::models::ShahSchema for #name {
-            fn shah_schema() -> #ci::models::Schema {#{if args.is_array {
-                let len = (args.max_bits / 8) as u64;
-                quote_into! {s +=
-                    #ci::models::Schema::Array {
-                        is_str: false,
-                        length: #len,
-                        kind: Box::new(#ci::models::Schema::U8),
-                    }
-                };
-            } else {
-                let syn::Type::Path(p) = args.inner else { unreachable!() };
-                let k = p.path.get_ident().unwrap().to_string().to_uppercase();
-                let kind = format_ident!("{k}");
-                quote_into! {s += #ci::models::Schema::#kind};
-            }}}
+            fn shah_schema() -> #ci::models::Schema {
+                #ci::models::Schema::Flags {
+                    kind: Box::new(#{if args.is_array {
+                        let len = (args.max_bits / 8) as u64;
+                        quote_into! {s +=
+                            #ci::models::Schema::Array {
+                                is_str: false,
+                                length: #len,
+                                kind: Box::new(#ci::models::Schema::U8),
+                            }
+                        };
+                    } else {
+                        let syn::Type::Path(p) = args.inner else { unreachable!() };
+                        let k = p.path.get_ident().unwrap().to_string().to_uppercase();
+                        let kind = format_ident!("{k}");
+                        quote_into! {s += #ci::models::Schema::#kind};
+                    }}),
+                    fields: vec![#schema_fields],
+                }
+            }
         }
     };
 
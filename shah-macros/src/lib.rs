@@ -160,11 +160,13 @@ pub fn entity(code: TokenStream) -> TokenStream {
 /// Derive macro generating an impl of the trait `Belt`.
 /// You can use `#[belt(next)]`, `#[belt(past)]` and `#[belt(buckle)]`
 /// to set custom fields for these methods.
+/// `#[belt(order)]` on a `u64` field keeps the belts of a buckle sorted.
 pub fn belt(code: TokenStream) -> TokenStream {
     let ci = crate_ident();
     let inp = syn::parse_macro_input!(code as syn::DeriveInput);
 
     let gene = parse_quote!(#ci::models::Gene);
+    let pu64 = parse_quote!(u64);
 
     let tr = Traitor::new(
         "belt",
@@ -173,6 +175,7 @@ pub fn belt(code: TokenStream) -> TokenStream {
             TraitorField::new("next", &gene, false),
             TraitorField::new("past", &gene, false),
             TraitorField::new("buckle", &gene, false),
+            TraitorField::optional("order", &pu64),
         ],
     );
     tr.derive(inp).unwrap_or_else(syn::Error::into_compile_error).into()
@@ -199,14 +202,20 @@ pub fn buckle(code: TokenStream) -> TokenStream {
 }
 
 #[proc_macro_derive(Duck, attributes(duck))]
+/// `#[duck(order)]` on a `u64` field makes the ponds of an origin sorted.
+/// the pond must have `#[pond(min)]` and `#[pond(max)]` fields
 pub fn duck(code: TokenStream) -> TokenStream {
     let ci = crate_ident();
     let inp = syn::parse_macro_input!(code as syn::DeriveInput);
     let gene = parse_quote!(#ci::models::Gene);
+    let pu64 = parse_quote!(u64);
     let tr = Traitor::new(
         "duck",
         parse_quote!(#ci::db::pond::Duck),
-        [TraitorField::new("pond", &gene, false)],
+        [
+            TraitorField::new("pond", &gene, false),
+            TraitorField::optional("order", &pu64),
+        ],
     );
     tr.derive(inp).unwrap_or_else(syn::Error::into_compile_error).into()
 }
@@ -217,7 +226,8 @@ pub fn pond(code: TokenStream) -> TokenStream {
     let inp = syn::parse_macro_input!(code as syn::DeriveInput);
 
     let gene = parse_quote!(#ci::models::Gene);
-    let pru8 = parse_quote!(u8);
+    let pu16 = parse_quote!(u16);
+    let pu64 = parse_quote!(u64);
     let gene_id = parse_quote!(#ci::models::GeneId);
 
     let tr = Traitor::new(
@@ -228,8 +238,10 @@ pub fn pond(code: TokenStream) -> TokenStream {
             TraitorField::new("past", &gene, false),
             TraitorField::new("origin", &gene, false),
             TraitorField::new("stack", &gene_id, true),
-            TraitorField::new("alive", &pru8, true),
-            TraitorField::new("empty", &pru8, true),
+            TraitorField::new("alive", &pu16, true),
+            TraitorField::new("empty", &pu16, true),
+            TraitorField::optional("min", &pu64),
+            TraitorField::optional("max", &pu64),
         ],
     );
     tr.derive(inp).unwrap_or_else(syn::Error::into_compile_error).into()
@@ -9,11 +9,19 @@ pub struct TraitorField<'a> {
     copy: bool,
     ty: &'a syn::Type,
     ident: syn::Ident,
+    /// only implemented when set with the attribute.
+    /// the trait must have a default that returns `None`
+    optional: Option<bool>,
 }
 
 impl<'a> TraitorField<'a> {
     pub fn new(name: &'static str, ty: &'a syn::Type, copy: bool) -> Self {
-        Self { name, ty, copy, ident: crate::ident!(name) }
+        Self { name, ty, copy, ident: crate::ident!(name), optional: None }
+    }
+
+    /// a getter returning `Option<ty>` and a `_mut` returning `Option<&mut ty>`
+    pub fn optional(name: &'static str, ty: &'a syn::Type) -> Self {
+        Self { optional: Some(false), ..Self::new(name, ty, true) }
     }
 }
 
@@ -60,6 +68,9 @@ impl<'a, const LEN: usize> Traitor<'a, LEN> {
                     if tf.name == kind {
                         found = true;
                         tf.ident = ident;
+                        if let Some(set) = tf.optional.as_mut() {
+                            *set = true;
+                        }
                         break;
                     }
                 }
@@ -74,8 +85,26 @@ impl<'a, const LEN: usize> Traitor<'a, LEN> {
 
         let mut s = TokenStream::new();
 
-        for TraitorField { ident, name, copy, ty } in self.fields {
+        for TraitorField { ident, name, copy, ty, optional } in self.fields {
             let getfn = crate::ident!(name);
+            match optional {
+                Some(false) => continue,
+                Some(true) => {
+                    let mutfn = format_ident!("{name}_mut");
+                    quote_into! {s +=
+                        fn #getfn(&self) -> Option<#ty> {
+                            Some(self.#ident)
+                        }
+
+                        fn #mutfn(&mut self) -> Option<&mut #ty> {
+                            Some(&mut self.#ident)
+                        }
+                    };
+                    continue;
+                }
+                None => {}
+            }
+
             let mutfn = format_ident!("{name}_mut");
             quote_into! {s +=
                 fn #getfn(&self) -> #{if !copy {quote_into!(s += &)}} #ty {
@@ -21,6 +21,8 @@ macro_rules! eint {
 pub struct ShahConfig {
     pub server: u32,
     pub data_dir: std::path::PathBuf,
+    /// key for gene tokens, from the optional `SHAH_TOKEN_KEY` env var
+    pub token_key: Option<crate::models::GeneTokenKey>,
 }
 
 impl ShahConfig {
@@ -47,6 +49,13 @@ impl ShahConfig {
             panic!("SHAH_SERVER_INDEX env must not be 0");
         }
 
-        STATE.get_or_init(|| Self { server, data_dir: data_dir.into() })
+        STATE.get_or_init(|| {
+            let token_key = std::env::var("SHAH_TOKEN_KEY")
+                .ok()
+                .filter(|v| !v.is_empty())
+                .map(|v| crate::models::GeneTokenKey::new(v.as_bytes()));
+
+            Self { server, data_dir: data_dir.into(), token_key }
+        })
     }
 }
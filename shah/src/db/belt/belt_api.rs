@@ -9,35 +9,122 @@ impl<
     BkS,
 > BeltDb<Bt, Bk, BtO, BkO, BtS, BkS>
 {
-    pub fn belt_add(
-        &mut self, buckle_gene: &Gene, belt: &mut Bt,
+    /// set `past` and `next` of a detached belt to where it belongs.
+    /// sorted belts go after the last belt with an order not greater
+    /// than theirs, so equal keys keep the insertion order.
+    /// without `near` the place is found through the lanes. from `near`
+    /// the search reads every belt on the way to the place
+    fn place(
+        &mut self, belt: &mut Bt, buckle: &Bk, near: &Gene,
     ) -> Result<(), ShahError> {
-        let mut buckle = Bk::default();
-        self.buckle.get(buckle_gene, &mut buckle)?;
+        let Some(key) = belt.order() else {
+            *belt.past_mut() = *buckle.tail();
+            belt.next_mut().clear();
+            return Ok(());
+        };
 
-        belt.entity_flags_mut().set_is_alive(true);
+        let mut temp = Bt::default();
+        let mut gene = *near;
+        if near.is_none() {
+            gene = self.lane_find(buckle, key)?;
+            *belt.next_mut() = *buckle.head();
+            if self.belt.get(&gene, &mut temp).onf()?.is_some() {
+                *belt.next_mut() = *temp.next();
+            }
+            *belt.past_mut() = gene;
+            return Ok(());
+        }
+
+        if self.belt.get(&gene, &mut temp).onf()?.is_none() {
+            belt.past_mut().clear();
+            *belt.next_mut() = *buckle.head();
+            return Ok(());
+        }
 
+        if temp.order().unwrap_or_default() <= key {
+            // forward to the last belt that is not greater
+            let mut next = *temp.next();
+            while next.is_some() {
+                if self.belt.get(&next, &mut temp).onf()?.is_none()
+                    || temp.order().unwrap_or_default() > key
+                {
+                    break;
+                }
+                gene = next;
+                next = *temp.next();
+            }
+            *belt.past_mut() = gene;
+            *belt.next_mut() = next;
+            return Ok(());
+        }
+
+        // backward to the first belt that is not greater
+        let mut next = gene;
+        gene = *temp.past();
+        while gene.is_some() {
+            if self.belt.get(&gene, &mut temp).onf()?.is_none() {
+                break;
+            }
+            if temp.order().unwrap_or_default() <= key {
+                *belt.past_mut() = gene;
+                *belt.next_mut() = next;
+                return Ok(());
+            }
+            next = gene;
+            gene = *temp.past();
+        }
+
+        belt.past_mut().clear();
+        *belt.next_mut() = next;
+        Ok(())
+    }
+
+    /// add a new belt to the buckle between its `past` and `next`
+    fn add_new(
+        &mut self, belt: &mut Bt, buckle: &mut Bk,
+    ) -> Result<(), ShahError> {
+        belt.entity_flags_mut().set_is_alive(true);
         *belt.buckle_mut() = *buckle.gene();
         *belt.growth_mut() = 0;
-        *belt.past_mut() = *buckle.tail();
-        belt.next_mut().clear();
 
         self.belt.add(belt)?;
 
-        if buckle.head().is_none() {
-            *buckle.head_mut() = *belt.gene();
+        let links = (*belt.past(), *belt.next());
+        self.add_link(belt, buckle)?;
+        if links != (*belt.past(), *belt.next()) {
+            self.belt.set_unchecked(belt)?;
         }
 
-        let mut sib = Bt::default();
-        let old_tail_gene = *buckle.tail();
-        *buckle.tail_mut() = *belt.gene();
-        *buckle.belt_count_mut() += 1;
+        Ok(())
+    }
+
+    /// sorted belts are placed through the lanes of the buckle,
+    /// a few reads per lane and not one per belt
+    pub fn belt_add(
+        &mut self, buckle_gene: &Gene, belt: &mut Bt,
+    ) -> Result<(), ShahError> {
+        self.belt_add_near(buckle_gene, &Gene::NONE, belt)
+    }
 
-        if self.belt.get(&old_tail_gene, &mut sib).onf()?.is_some() {
-            *sib.next_mut() = *buckle.tail();
-            self.belt.set(&mut sib)?;
+    /// add a sorted belt, searching for its place from the `near` belt.
+    /// costs one read per belt between `near` and the place
+    pub fn belt_add_near(
+        &mut self, buckle_gene: &Gene, near: &Gene, belt: &mut Bt,
+    ) -> Result<(), ShahError> {
+        let mut buckle = Bk::default();
+        self.buckle.get(buckle_gene, &mut buckle)?;
+
+        if near.is_some() {
+            let mut temp = Bt::default();
+            self.belt.get(near, &mut temp)?;
+            if temp.buckle() != buckle.gene() {
+                return Err(SystemError::BeltBuckleMismatch)?;
+            }
         }
 
+        self.place(belt, &buckle, near)?;
+        self.add_new(belt, &mut buckle)?;
+
         self.buckle.set(&mut buckle)
     }
 
@@ -48,26 +135,8 @@ impl<
         self.buckle.get(buckle_gene, &mut buckle)?;
 
         for belt in belts {
-            belt.entity_flags_mut().set_is_alive(true);
-            *belt.buckle_mut() = *buckle.gene();
-            *belt.growth_mut() = 0;
-            *belt.past_mut() = *buckle.tail();
-            belt.next_mut().clear();
-
-            self.belt.add(belt)?;
-            if buckle.head().is_none() {
-                *buckle.head_mut() = *belt.gene();
-            }
-
-            let old_tail_gene = *buckle.tail();
-            *buckle.tail_mut() = *belt.gene();
-            *buckle.belt_count_mut() += 1;
-
-            let mut tail = Bt::default();
-            if self.belt.get(&old_tail_gene, &mut tail).onf()?.is_some() {
-                *tail.next_mut() = *buckle.tail();
-                self.belt.set(&mut tail)?;
-            }
+            self.place(belt, &buckle, &Gene::NONE)?;
+            self.add_new(belt, &mut buckle)?;
         }
 
         self.buckle.set(&mut buckle)
@@ -83,6 +152,8 @@ impl<
         self.belt.count()
     }
 
+    /// a sorted belt with a new order is moved to its new place,
+    /// found through the lanes
     pub fn belt_set(&mut self, belt: &mut Bt) -> Result<(), ShahError> {
         if !belt.entity_flags().is_alive() {
             log::error!("{} DeadSet: using set to delete", self.ls);
@@ -97,12 +168,25 @@ impl<
         *belt.past_mut() = *old_belt.past();
         *belt.buckle_mut() = *old_belt.buckle();
 
-        self.belt.set_unchecked(belt)
+        if belt.order() == old_belt.order() {
+            return self.belt.set_unchecked(belt);
+        }
+
+        let mut buckle = Bk::default();
+        self.buckle.get(belt.buckle(), &mut buckle)?;
+
+        self.del_link(&mut old_belt, &mut buckle)?;
+        self.place(belt, &buckle, &Gene::NONE)?;
+        self.add_link(belt, &mut buckle)?;
+
+        self.belt.set_unchecked(belt)?;
+        self.buckle.set_unchecked(&mut buckle)
     }
 
     fn del_link(
         &mut self, belt: &mut Bt, buckle: &mut Bk,
     ) -> Result<(), ShahError> {
+        self.lane_del(belt)?;
         *buckle.belt_count_mut() = buckle.belt_count().saturating_sub(1);
 
         if buckle.head() == belt.gene() {
@@ -150,13 +234,81 @@ impl<
         self.belt.list(id, result)
     }
 
+    /// link a detached belt between its `past` and `next` and update
+    /// the neighbours and the buckle. the belt itself is not written
+    fn add_link(
+        &mut self, belt: &mut Bt, buckle: &mut Bk,
+    ) -> Result<(), ShahError> {
+        *buckle.belt_count_mut() += 1;
+
+        let mut temp = Bt::default();
+
+        if self.belt.get(belt.past(), &mut temp).onf()?.is_some() {
+            *temp.next_mut() = *belt.gene();
+            self.belt.set_unchecked(&mut temp)?;
+        } else {
+            belt.past_mut().clear();
+            *buckle.head_mut() = *belt.gene();
+        }
+
+        if self.belt.get(belt.next(), &mut temp).onf()?.is_some() {
+            *temp.past_mut() = *belt.gene();
+            self.belt.set_unchecked(&mut temp)?;
+        } else {
+            belt.next_mut().clear();
+            *buckle.tail_mut() = *belt.gene();
+        }
+
+        self.lane_add(belt)
+    }
+
+    fn belt_insert(
+        &mut self, anchor_gene: &Gene, belt: &mut Bt, after: bool,
+    ) -> Result<(), ShahError> {
+        if belt.order().is_some() {
+            return Err(SystemError::BeltIsSorted)?;
+        }
+
+        let mut anchor = Bt::default();
+        let mut buckle = Bk::default();
+        self.belt.get(anchor_gene, &mut anchor)?;
+        self.buckle.get(anchor.buckle(), &mut buckle)?;
+
+        if after {
+            *belt.past_mut() = *anchor.gene();
+            *belt.next_mut() = *anchor.next();
+        } else {
+            *belt.past_mut() = *anchor.past();
+            *belt.next_mut() = *anchor.gene();
+        }
+
+        self.add_new(belt, &mut buckle)?;
+        self.buckle.set_unchecked(&mut buckle)
+    }
+
+    /// add a new belt right after the anchor, in the anchor's buckle
+    pub fn belt_insert_after(
+        &mut self, anchor_gene: &Gene, belt: &mut Bt,
+    ) -> Result<(), ShahError> {
+        self.belt_insert(anchor_gene, belt, true)
+    }
+
+    /// add a new belt right before the anchor, in the anchor's buckle
+    pub fn belt_insert_before(
+        &mut self, anchor_gene: &Gene, belt: &mut Bt,
+    ) -> Result<(), ShahError> {
+        self.belt_insert(anchor_gene, belt, false)
+    }
+
     pub fn move_to_tail(
         &mut self, gene: &Gene, belt: &mut Bt,
     ) -> Result<(), ShahError> {
         let mut buckle = Bk::default();
-        let mut temp = Bt::default();
 
         self.belt.get(gene, belt)?;
+        if belt.order().is_some() {
+            return Err(SystemError::BeltIsSorted)?;
+        }
         self.buckle.get(belt.buckle(), &mut buckle)?;
 
         if buckle.tail() == belt.gene() {
@@ -165,16 +317,37 @@ impl<
 
         self.del_link(belt, &mut buckle)?;
 
+        *belt.past_mut() = *buckle.tail();
         belt.next_mut().clear();
-        belt.past_mut().clear();
 
-        if self.belt.get(buckle.tail(), &mut temp).onf()?.is_some() {
-            *temp.next_mut() = *belt.gene();
-            *belt.past_mut() = *temp.gene();
-            self.belt.set_unchecked(&mut temp)?;
+        self.add_link(belt, &mut buckle)?;
+        self.belt.set_unchecked(belt)?;
+        self.buckle.set_unchecked(&mut buckle)?;
+
+        Ok(())
+    }
+
+    pub fn move_to_head(
+        &mut self, gene: &Gene, belt: &mut Bt,
+    ) -> Result<(), ShahError> {
+        let mut buckle = Bk::default();
+
+        self.belt.get(gene, belt)?;
+        if belt.order().is_some() {
+            return Err(SystemError::BeltIsSorted)?;
+        }
+        self.buckle.get(belt.buckle(), &mut buckle)?;
+
+        if buckle.head() == belt.gene() {
+            return Ok(());
         }
 
-        *buckle.tail_mut() = *belt.gene();
+        self.del_link(belt, &mut buckle)?;
+
+        belt.past_mut().clear();
+        *belt.next_mut() = *buckle.head();
+
+        self.add_link(belt, &mut buckle)?;
         self.belt.set_unchecked(belt)?;
         self.buckle.set_unchecked(&mut buckle)?;
 
@@ -184,7 +357,6 @@ impl<
     pub fn change_buckle(
         &mut self, gene: &Gene, new_buckle_gene: &Gene, belt: &mut Bt,
     ) -> Result<(), ShahError> {
-        let mut temp = Bt::default();
         let mut old_buckle = Bk::default();
         let mut new_buckle = Bk::default();
 
@@ -195,22 +367,13 @@ impl<
         self.del_link(belt, &mut old_buckle)?;
         self.buckle.set_unchecked(&mut old_buckle)?;
 
-        belt.next_mut().clear();
-        belt.past_mut().clear();
-
-        if self.belt.get(new_buckle.tail(), &mut temp).onf()?.is_some() {
-            *temp.next_mut() = *belt.gene();
-            *belt.past_mut() = *temp.gene();
-            self.belt.set_unchecked(&mut temp)?;
-        }
-
-        if new_buckle.head().is_none() {
-            *new_buckle.head_mut() = *belt.gene();
+        if new_buckle.gene() == old_buckle.gene() {
+            new_buckle.clone_from(&old_buckle);
         }
 
-        *new_buckle.belt_count_mut() += 1;
-        *new_buckle.tail_mut() = *belt.gene();
         *belt.buckle_mut() = *new_buckle.gene();
+        self.place(belt, &new_buckle, &Gene::NONE)?;
+        self.add_link(belt, &mut new_buckle)?;
 
         self.belt.set_unchecked(belt)?;
         self.buckle.set_unchecked(&mut new_buckle)?;
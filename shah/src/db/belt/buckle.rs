@@ -20,6 +20,7 @@ impl<
             *buckle.belt_count_mut() = 0;
             buckle.gene_mut().clear();
             self.buckle.add(buckle)?;
+            self.lanes.buckle_clear(buckle.gene())?;
         }
 
         Ok(())
@@ -64,21 +65,14 @@ impl<
         self.buckle.list(id, result)
     }
 
-    /// this will cascade all the belts under this buckle
+    /// delete the buckle now and its belts in the background.
+    /// the sweep is persisted and resumes after a restart
     pub fn buckle_del(&mut self, gene: &Gene) -> Result<(), ShahError> {
         let mut buckle = Bk::default();
         self.buckle.get(gene, &mut buckle)?;
 
-        let mut belt_gene = *buckle.head();
-        let mut belt = Bt::default();
-        loop {
-            if let Err(e) = self.belt.del(&belt_gene, &mut belt) {
-                if e.is_not_found() {
-                    break;
-                }
-                return Err(e)?;
-            }
-            belt_gene = *belt.next();
+        if buckle.head().is_some() {
+            self.sweeper.add(buckle.gene(), buckle.head())?;
         }
 
         self.buckle.del_unchecked(&mut buckle)
@@ -0,0 +1,473 @@
+//! byte storage on a [`BeltClothDb`].
+//! every belt is full except the tail and `buckle.length` is the size
+
+use super::*;
+use crate::SystemError;
+use crate::db::belt::BeltWalk;
+
+const WALK_PAGE: usize = 8;
+
+/// the length of a blob of `length` bytes after adding `add` bytes
+fn blob_len(length: u32, add: usize) -> Result<u32, ShahError> {
+    let len = u32::try_from(add).ok().and_then(|add| length.checked_add(add));
+    let Some(len) = len else { return Err(SystemError::BlobTooBig)? };
+    Ok(len)
+}
+
+/// every belt of a blob is full except the tail, a cloth with more
+/// belts than that has old ones after its end
+fn is_stale<const S: usize>(buckle: &ClothBuckle) -> bool {
+    buckle.chunks > (buckle.length as u64).div_ceil(S as u64)
+}
+
+impl<const S: usize> BeltClothDb<S> {
+    /// call `each` with the offset and the belt for every belt of the blob
+    /// until it returns false
+    fn each_belt(
+        &mut self, bg: &Gene, mut each: impl FnMut(u64, &ClothBelt<S>) -> bool,
+    ) -> Result<(), ShahError> {
+        let mut page = vec![ClothBelt::<S>::default(); WALK_PAGE];
+        let mut from = BeltWalk::HEAD;
+        let mut pos = 0u64;
+        loop {
+            let count = self.walk(bg, &from, &mut page)?;
+            for belt in page[..count].iter() {
+                if !each(pos, belt) {
+                    return Ok(());
+                }
+                pos += belt.length as u64;
+            }
+            if count < page.len() {
+                return Ok(());
+            }
+            from = BeltWalk::after(page[count - 1].gene);
+        }
+    }
+
+    pub fn read(&mut self, bg: &Gene) -> Result<Vec<u8>, ShahError> {
+        self.read_range(bg, 0, usize::MAX)
+    }
+
+    /// read at most `len` bytes starting at `offset`
+    pub fn read_range(
+        &mut self, bg: &Gene, offset: u64, len: usize,
+    ) -> Result<Vec<u8>, ShahError> {
+        let mut buckle = ClothBuckle::default();
+        self.buckle_get(bg, &mut buckle)?;
+
+        let end = offset.saturating_add(len as u64).min(buckle.length as u64);
+        if offset >= end {
+            return Ok(Vec::new());
+        }
+
+        let mut data = Vec::with_capacity((end - offset) as usize);
+        self.each_belt(bg, |pos, belt| {
+            let len = (belt.length as usize).min(S) as u64;
+            if pos + len > offset {
+                let a = offset.saturating_sub(pos) as usize;
+                let b = ((end - pos) as usize).min(len as usize);
+                data.extend_from_slice(&belt.data.raw()[a..b]);
+            }
+            pos + len < end
+        })?;
+
+        Ok(data)
+    }
+
+    /// delete every belt after `last`, all of them when `last` is none
+    fn cut_after(&mut self, bg: &Gene, last: &Gene) -> Result<(), ShahError> {
+        let mut buckle = ClothBuckle::default();
+        let mut belt = ClothBelt::<S>::default();
+        loop {
+            self.buckle_get(bg, &mut buckle)?;
+            if buckle.tail.is_none() || buckle.tail == *last {
+                return Ok(());
+            }
+            self.belt_del(&buckle.tail, &mut belt)?;
+        }
+    }
+
+    /// cloths written before blobs kept their old belts after the end,
+    /// hidden by `is_end`. free them so the tail is the last belt
+    fn cut_stale(&mut self, buckle: &mut ClothBuckle) -> Result<(), ShahError> {
+        if !is_stale::<S>(buckle) {
+            return Ok(());
+        }
+
+        let bg = buckle.gene;
+        let mut last = Gene::NONE;
+        if buckle.length > 0 {
+            self.each_belt(&bg, |_, belt| {
+                last = belt.gene;
+                !belt.flags.is_end()
+            })?;
+        }
+        self.cut_after(&bg, &last)?;
+        self.buckle_get(&bg, buckle)
+    }
+
+    /// shrink the blob to `len` bytes and free the belts after it.
+    /// a larger `len` does nothing
+    pub fn truncate(&mut self, bg: &Gene, len: u64) -> Result<(), ShahError> {
+        let mut buckle = ClothBuckle::default();
+        self.buckle_get(bg, &mut buckle)?;
+        if len >= buckle.length as u64 {
+            return Ok(());
+        }
+
+        let mut last = None;
+        if len > 0 {
+            self.each_belt(bg, |pos, belt| {
+                if pos + belt.length as u64 >= len {
+                    last = Some((pos, *belt));
+                    return false;
+                }
+                true
+            })?;
+        }
+
+        let last_gene = match last {
+            Some((pos, mut belt)) => {
+                let keep = (len - pos) as usize;
+                belt.data.raw_mut()[keep..].fill(0);
+                belt.length = keep as u16;
+                belt.flags.set_is_end(true);
+                self.belt_set(&mut belt)?;
+                belt.gene
+            }
+            None => Gene::NONE,
+        };
+
+        self.cut_after(bg, &last_gene)?;
+
+        buckle.length = len as u32;
+        self.buckle_set(&mut buckle)
+    }
+
+    /// add the data to the end of the blob, filling the tail belt first
+    pub fn append(&mut self, bg: &Gene, data: &[u8]) -> Result<(), ShahError> {
+        let mut buckle = ClothBuckle::default();
+        self.buckle_get(bg, &mut buckle)?;
+        if data.is_empty() {
+            return Ok(());
+        }
+        let length = blob_len(buckle.length, data.len())?;
+        self.cut_stale(&mut buckle)?;
+
+        let mut rest = data;
+        let mut tail = ClothBelt::<S>::default();
+        if self.belt_get(&buckle.tail, &mut tail).onf()?.is_some() {
+            let used = (tail.length as usize).min(S);
+            let fill = (S - used).min(rest.len());
+            tail.data.raw_mut()[used..used + fill]
+                .copy_from_slice(&rest[..fill]);
+            tail.length = (used + fill) as u16;
+            rest = &rest[fill..];
+            tail.flags.set_is_end(rest.is_empty());
+            self.belt_set(&mut tail)?;
+        }
+
+        let mut it = rest.chunks(S).peekable();
+        while let Some(x) = it.next() {
+            let mut cloth = ClothBelt::<S>::default();
+            cloth.data.raw_mut()[..x.len()].copy_from_slice(x);
+            cloth.length = x.len() as u16;
+            cloth.flags.set_is_end(it.peek().is_none());
+            self.belt_add(bg, &mut cloth)?;
+        }
+
+        buckle.length = length;
+        self.buckle_set(&mut buckle)
+    }
+
+    /// replace the content of the blob. the existing belts are reused
+    /// and the ones that are not needed anymore are freed
+    pub fn write(&mut self, bg: &Gene, data: &[u8]) -> Result<(), ShahError> {
+        blob_len(0, data.len())?;
+        let mut buckle = ClothBuckle::default();
+        self.buckle_get(bg, &mut buckle)?;
+
+        let mut gene = buckle.head;
+        let mut last = Gene::NONE;
+        let mut written = 0usize;
+        let mut cloth = ClothBelt::<S>::default();
+        let mut it = data.chunks(S).peekable();
+        while gene.is_some() {
+            let Some(x) = it.next() else { break };
+            if self.belt_get(&gene, &mut cloth).onf()?.is_none() {
+                break;
+            }
+
+            cloth.data.raw_mut()[x.len()..].fill(0);
+            cloth.data.raw_mut()[..x.len()].copy_from_slice(x);
+            cloth.length = x.len() as u16;
+            cloth.flags.set_is_end(it.peek().is_none());
+            self.belt_set(&mut cloth)?;
+
+            written += x.len();
+            last = cloth.gene;
+            gene = cloth.next;
+        }
+
+        self.cut_after(bg, &last)?;
+
+        buckle.length = written as u32;
+        self.buckle_set(&mut buckle)?;
+
+        self.append(bg, &data[written..])
+    }
+
+    /// a streaming reader over the whole blob
+    pub fn reader(
+        &mut self, bg: &Gene,
+    ) -> Result<BlobReader<'_, S>, ShahError> {
+        let mut buckle = ClothBuckle::default();
+        self.buckle_get(bg, &mut buckle)?;
+
+        Ok(BlobReader {
+            next: buckle.head,
+            left: buckle.length as u64,
+            belt: Box::default(),
+            pos: 0,
+            db: self,
+        })
+    }
+}
+
+/// [`std::io::Read`] over a blob, one belt is read at a time
+pub struct BlobReader<'a, const S: usize> {
+    db: &'a mut BeltClothDb<S>,
+    belt: Box<ClothBelt<S>>,
+    next: Gene,
+    pos: usize,
+    left: u64,
+}
+
+impl<const S: usize> std::io::Read for BlobReader<'_, S> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut len = (self.belt.length as usize).min(S);
+        while self.pos >= len {
+            if self.left == 0 || self.next.is_none() {
+                return Ok(0);
+            }
+            let next = self.next;
+            self.db
+                .belt_get(&next, &mut self.belt)
+                .map_err(|e| std::io::Error::other(format!("{e:?}")))?;
+            self.next = self.belt.next;
+            self.pos = 0;
+            len = (self.belt.length as usize).min(S);
+        }
+
+        let n = buf.len().min(len - self.pos).min(self.left as usize);
+        buf[..n].copy_from_slice(&self.belt.data.raw()[self.pos..self.pos + n]);
+        self.pos += n;
+        self.left -= n as u64;
+        Ok(n)
+    }
+}
+
+impl<E: IsNotFound + From<u16> + Copy, const S: usize> ClothClient<E, S> {
+    /// the last belt that is kept when truncating to `len` bytes
+    fn belt_at(
+        &self, taker: &Taker, buckle: &ClothBuckle, len: u64,
+    ) -> Result<Option<(u64, ClothBelt<S>)>, ClientError<E>> {
+        let mut gene = buckle.head;
+        let mut pos = 0u64;
+        while gene.is_some() {
+            let Some(cloth) = (self.belt_get)(taker, &gene).onf()? else {
+                break;
+            };
+            if pos + cloth.length as u64 >= len {
+                return Ok(Some((pos, cloth)));
+            }
+            pos += cloth.length as u64;
+            gene = cloth.next;
+        }
+        Ok(None)
+    }
+
+    fn cut_after(
+        &self, taker: &Taker, bg: &Gene, last: &Gene,
+    ) -> Result<(), ClientError<E>> {
+        loop {
+            let buckle = (self.buckle_get)(taker, bg)?;
+            if buckle.tail.is_none() || buckle.tail == *last {
+                return Ok(());
+            }
+            (self.belt_del)(taker, &buckle.tail)?;
+        }
+    }
+
+    /// free the old belts after the end of a cloth written before blobs,
+    /// hidden by `is_end`
+    fn cut_stale(
+        &self, taker: &Taker, buckle: &mut ClothBuckle,
+    ) -> Result<(), ClientError<E>> {
+        if !is_stale::<S>(buckle) {
+            return Ok(());
+        }
+
+        let mut gene = buckle.head;
+        let mut last = Gene::NONE;
+        while buckle.length > 0 && gene.is_some() {
+            let Some(cloth) = (self.belt_get)(taker, &gene).onf()? else {
+                break;
+            };
+            last = cloth.gene;
+            if cloth.flags.is_end() {
+                break;
+            }
+            gene = cloth.next;
+        }
+        self.cut_after(taker, &buckle.gene, &last)?;
+        *buckle = (self.buckle_get)(taker, &buckle.gene)?;
+        Ok(())
+    }
+
+    pub fn read(
+        &self, taker: &Taker, bg: &Gene,
+    ) -> Result<Vec<u8>, ClientError<E>> {
+        self.read_range(taker, bg, 0, usize::MAX)
+    }
+
+    /// read at most `len` bytes starting at `offset`
+    pub fn read_range(
+        &self, taker: &Taker, bg: &Gene, offset: u64, len: usize,
+    ) -> Result<Vec<u8>, ClientError<E>> {
+        bg.validate()?;
+        let buckle = (self.buckle_get)(taker, bg)?;
+
+        let end = offset.saturating_add(len as u64).min(buckle.length as u64);
+        if offset >= end {
+            return Ok(Vec::new());
+        }
+
+        let mut data = Vec::with_capacity((end - offset) as usize);
+        let mut gene = buckle.head;
+        let mut pos = 0u64;
+        while gene.is_some() && pos < end {
+            let Some(cloth) = (self.belt_get)(taker, &gene).onf()? else {
+                break;
+            };
+            let len = (cloth.length as usize).min(S) as u64;
+            if pos + len > offset {
+                let a = offset.saturating_sub(pos) as usize;
+                let b = ((end - pos) as usize).min(len as usize);
+                data.extend_from_slice(&cloth.data.raw()[a..b]);
+            }
+            pos += len;
+            gene = cloth.next;
+        }
+
+        Ok(data)
+    }
+
+    /// shrink the blob to `len` bytes and free the belts after it
+    pub fn truncate(
+        &self, taker: &Taker, bg: &Gene, len: u64,
+    ) -> Result<(), ClientError<E>> {
+        bg.validate()?;
+        let mut buckle = (self.buckle_get)(taker, bg)?;
+        if len >= buckle.length as u64 {
+            return Ok(());
+        }
+
+        let last =
+            if len > 0 { self.belt_at(taker, &buckle, len)? } else { None };
+        let last_gene = match last {
+            Some((pos, mut cloth)) => {
+                let keep = (len - pos) as usize;
+                cloth.data.raw_mut()[keep..].fill(0);
+                cloth.length = keep as u16;
+                cloth.flags.set_is_end(true);
+                (self.belt_set)(taker, &cloth)?;
+                cloth.gene
+            }
+            None => Gene::NONE,
+        };
+
+        self.cut_after(taker, bg, &last_gene)?;
+
+        buckle.length = len as u32;
+        (self.buckle_set)(taker, &buckle)?;
+        Ok(())
+    }
+
+    /// add the data to the end of the blob, filling the tail belt first
+    pub fn append(
+        &self, taker: &Taker, bg: &Gene, data: &[u8],
+    ) -> Result<(), ClientError<E>> {
+        bg.validate()?;
+        let mut buckle = (self.buckle_get)(taker, bg)?;
+        if data.is_empty() {
+            return Ok(());
+        }
+        let length = blob_len(buckle.length, data.len())?;
+        self.cut_stale(taker, &mut buckle)?;
+
+        let mut rest = data;
+        if let Some(mut tail) = (self.belt_get)(taker, &buckle.tail).onf()? {
+            let used = (tail.length as usize).min(S);
+            let fill = (S - used).min(rest.len());
+            tail.data.raw_mut()[used..used + fill]
+                .copy_from_slice(&rest[..fill]);
+            tail.length = (used + fill) as u16;
+            rest = &rest[fill..];
+            tail.flags.set_is_end(rest.is_empty());
+            (self.belt_set)(taker, &tail)?;
+        }
+
+        let mut it = rest.chunks(S).peekable();
+        while let Some(x) = it.next() {
+            let mut cloth = ClothBelt::<S>::default();
+            cloth.data.raw_mut()[..x.len()].copy_from_slice(x);
+            cloth.length = x.len() as u16;
+            cloth.entity_flags_mut().set_is_alive(true);
+            cloth.flags.set_is_end(it.peek().is_none());
+            (self.belt_add)(taker, bg, &cloth)?;
+        }
+
+        buckle.length = length;
+        (self.buckle_set)(taker, &buckle)?;
+        Ok(())
+    }
+
+    /// replace the content of the blob, reusing the existing belts
+    /// and freeing the ones that are not needed anymore
+    pub fn write(
+        &self, taker: &Taker, bg: &Gene, data: &[u8],
+    ) -> Result<(), ClientError<E>> {
+        bg.validate()?;
+        blob_len(0, data.len())?;
+        let mut buckle = (self.buckle_get)(taker, bg)?;
+
+        let mut gene = buckle.head;
+        let mut last = Gene::NONE;
+        let mut written = 0usize;
+        let mut it = data.chunks(S).peekable();
+        while gene.is_some() {
+            let Some(x) = it.next() else { break };
+            let Some(mut cloth) = (self.belt_get)(taker, &gene).onf()? else {
+                break;
+            };
+
+            cloth.data.raw_mut()[x.len()..].fill(0);
+            cloth.data.raw_mut()[..x.len()].copy_from_slice(x);
+            cloth.length = x.len() as u16;
+            cloth.flags.set_is_end(it.peek().is_none());
+            (self.belt_set)(taker, &cloth)?;
+
+            written += x.len();
+            last = cloth.gene;
+            gene = cloth.next;
+        }
+
+        self.cut_after(taker, bg, &last)?;
+
+        buckle.length = written as u32;
+        (self.buckle_set)(taker, &buckle)?;
+
+        self.append(taker, bg, &data[written..])
+    }
+}
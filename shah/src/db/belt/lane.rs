@@ -0,0 +1,223 @@
+//! a skip list over the sorted belts of each buckle. the belt chain is
+//! the bottom lane and a belt is on `n` more lanes when the first `2n`
+//! bits of its pepper are zero, so every lane has about a quarter of the
+//! belts of the one below it. finding the place of a key costs about
+//! `4 * LANES` reads instead of a walk over the whole chain.
+//!
+//! the lanes are only a way to get close, every belt they lead to is
+//! checked and the bottom lane decides. after a crash in the middle of
+//! an update a lane can be cut short, which only makes searches slower
+
+use super::*;
+use crate::models::Binary;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+/// number of lanes above the belt chain
+pub(super) const LANES: usize = 8;
+
+/// the links of a belt on the lanes it is on.
+/// for a buckle `next` is the first belt of each lane
+#[crate::model]
+#[derive(Debug)]
+pub(super) struct BeltLane {
+    next: [Gene; LANES],
+    past: [Gene; LANES],
+}
+
+/// the lanes of the belts and of the buckles, one record per gene id
+#[derive(Debug)]
+pub(super) struct BeltLanes {
+    belt: File,
+    buckle: File,
+}
+
+/// the neighbours of a key on every lane, none is the buckle
+type LanePath = [(Gene, Gene); LANES];
+
+/// number of lanes a belt is on, above the chain
+fn lane_level(gene: &Gene) -> usize {
+    let [a, b, c] = gene.pepper;
+    let bits = u32::from_le_bytes([a, b, c, 0]) | 1 << 24;
+    (bits.trailing_zeros() as usize / 2).min(LANES)
+}
+
+impl BeltLanes {
+    pub(super) fn new(data_path: &Path) -> Result<Self, ShahError> {
+        let open = |name: &str| {
+            std::fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(data_path.join(name))
+        };
+        Ok(Self { belt: open("belt.lane")?, buckle: open("buckle.lane")? })
+    }
+
+    fn get(file: &File, id: GeneId) -> Result<BeltLane, ShahError> {
+        let mut lane = BeltLane::default();
+        match file.read_exact_at(lane.as_binary_mut(), id.0 * BeltLane::N) {
+            Ok(()) => Ok(lane),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                Ok(BeltLane::default())
+            }
+            Err(e) => Err(e)?,
+        }
+    }
+
+    fn set(file: &File, id: GeneId, lane: &BeltLane) -> Result<(), ShahError> {
+        file.write_all_at(lane.as_binary(), id.0 * BeltLane::N)?;
+        Ok(())
+    }
+
+    /// the lanes of a belt, or of the buckle when `gene` is none
+    fn lane(&self, buckle: &Gene, gene: &Gene) -> Result<BeltLane, ShahError> {
+        match gene.is_some() {
+            true => Self::get(&self.belt, gene.id),
+            false => Self::get(&self.buckle, buckle.id),
+        }
+    }
+
+    fn set_lane(
+        &self, buckle: &Gene, gene: &Gene, lane: &BeltLane,
+    ) -> Result<(), ShahError> {
+        match gene.is_some() {
+            true => Self::set(&self.belt, gene.id, lane),
+            false => Self::set(&self.buckle, buckle.id, lane),
+        }
+    }
+
+    /// a new buckle may reuse the id of a deleted one
+    pub(super) fn buckle_clear(&self, buckle: &Gene) -> Result<(), ShahError> {
+        Self::set(&self.buckle, buckle.id, &BeltLane::default())
+    }
+}
+
+impl<
+    Bt: Belt + EntityKochFrom<BtO, BtS>,
+    Bk: Buckle + EntityKochFrom<BkO, BkS>,
+    BtO: Belt,
+    BkO: Buckle,
+    BtS,
+    BkS,
+> BeltDb<Bt, Bk, BtO, BkO, BtS, BkS>
+{
+    /// the last belt on each lane with an order not greater than `key`
+    /// and the belt after it. returns the last one on the lowest lane
+    fn lane_path(
+        &mut self, buckle: &Gene, key: u64, path: &mut LanePath,
+    ) -> Result<Gene, ShahError> {
+        let mut gene = Gene::NONE;
+        let mut lane = self.lanes.lane(buckle, &gene)?;
+        let mut temp = Bt::default();
+        for x in (0..LANES).rev() {
+            let mut next = lane.next[x];
+            // a broken lane could link back to an equal key
+            for _ in 0..BLOCK_SIZE {
+                if next.is_none() {
+                    break;
+                }
+                if self.belt.get(&next, &mut temp).onf()?.is_none()
+                    || temp.buckle() != buckle
+                    || lane_level(&next) <= x
+                {
+                    next.clear();
+                    break;
+                }
+                if temp.order().unwrap_or_default() > key {
+                    break;
+                }
+                gene = next;
+                lane = self.lanes.lane(buckle, &gene)?;
+                next = lane.next[x];
+            }
+            path[x] = (gene, next);
+        }
+        Ok(gene)
+    }
+
+    /// the last belt of the buckle with an order not greater than `key`,
+    /// none when every belt is greater
+    pub(super) fn lane_find(
+        &mut self, buckle: &Bk, key: u64,
+    ) -> Result<Gene, ShahError> {
+        let mut path = LanePath::default();
+        let mut gene = self.lane_path(buckle.gene(), key, &mut path)?;
+
+        let mut temp = Bt::default();
+        let mut next = *buckle.head();
+        if gene.is_some() {
+            self.belt.get(&gene, &mut temp)?;
+            next = *temp.next();
+        }
+        while next.is_some() {
+            if self.belt.get(&next, &mut temp).onf()?.is_none()
+                || temp.order().unwrap_or_default() > key
+            {
+                break;
+            }
+            gene = next;
+            next = *temp.next();
+        }
+        Ok(gene)
+    }
+
+    /// put a sorted belt that is already in the chain on its lanes
+    pub(super) fn lane_add(&mut self, belt: &Bt) -> Result<(), ShahError> {
+        let level = lane_level(belt.gene());
+        let Some(key) = belt.order().filter(|_| level > 0) else {
+            return Ok(());
+        };
+
+        let mut path = LanePath::default();
+        self.lane_path(belt.buckle(), key, &mut path)?;
+
+        let mut lane = BeltLane::default();
+        for (x, (past, next)) in path.iter().enumerate().take(level) {
+            lane.past[x] = *past;
+            lane.next[x] = *next;
+
+            let mut temp = self.lanes.lane(belt.buckle(), past)?;
+            temp.next[x] = *belt.gene();
+            self.lanes.set_lane(belt.buckle(), past, &temp)?;
+
+            if next.is_some() {
+                let mut temp = self.lanes.lane(belt.buckle(), next)?;
+                temp.past[x] = *belt.gene();
+                self.lanes.set_lane(belt.buckle(), next, &temp)?;
+            }
+        }
+        self.lanes.set_lane(belt.buckle(), belt.gene(), &lane)
+    }
+
+    /// take a sorted belt off its lanes before it leaves the chain
+    pub(super) fn lane_del(&mut self, belt: &Bt) -> Result<(), ShahError> {
+        let level = lane_level(belt.gene());
+        if belt.order().is_none() || level == 0 {
+            return Ok(());
+        }
+
+        let buckle = belt.buckle();
+        let lane = self.lanes.lane(buckle, belt.gene())?;
+        for x in 0..level {
+            let (past, next) = (lane.past[x], lane.next[x]);
+
+            let mut temp = self.lanes.lane(buckle, &past)?;
+            if temp.next[x] == *belt.gene() {
+                temp.next[x] = next;
+                self.lanes.set_lane(buckle, &past, &temp)?;
+            }
+
+            if next.is_some() {
+                let mut temp = self.lanes.lane(buckle, &next)?;
+                if temp.past[x] == *belt.gene() {
+                    temp.past[x] = past;
+                    self.lanes.set_lane(buckle, &next, &temp)?;
+                }
+            }
+        }
+        self.lanes.set_lane(buckle, belt.gene(), &BeltLane::default())
+    }
+}
@@ -2,15 +2,21 @@ use crate::config::ShahConfig;
 use crate::db::entity::{
     EntityCount, EntityDb, EntityFlags, EntityItem, EntityKochFrom,
 };
+use crate::db::sweep::Sweeper;
 use crate::models::{Gene, GeneId, Performed, Task, TaskList, Worker};
 use crate::{
-    IsNotFound, OptNotFound, PAGE_SIZE, ShahError, SystemError, utils,
+    BLOCK_SIZE, IsNotFound, OptNotFound, PAGE_SIZE, ShahError, SystemError,
+    utils,
 };
 
 mod belt_api;
 mod buckle;
 pub mod cloth;
+mod lane;
 mod options;
+mod walk;
+
+pub use walk::{BeltClient, BeltWalk};
 
 pub trait Buckle: EntityItem {
     fn head(&self) -> &Gene;
@@ -43,6 +49,14 @@ pub trait Belt: EntityItem {
     fn past_mut(&mut self) -> &mut Gene;
     fn buckle(&self) -> &Gene;
     fn buckle_mut(&mut self) -> &mut Gene;
+    /// the sort key of this belt. belts with an order are kept sorted
+    /// from head to tail and can not be placed by position
+    fn order(&self) -> Option<u64> {
+        None
+    }
+    fn order_mut(&mut self) -> Option<&mut u64> {
+        None
+    }
 }
 
 #[derive(Debug)]
@@ -56,8 +70,10 @@ pub struct BeltDb<
 > {
     buckle: EntityDb<Bk, BkO, BkS>,
     belt: EntityDb<Bt, BtO, BtS>,
+    lanes: lane::BeltLanes,
+    sweeper: Sweeper,
     ls: String,
-    tasks: TaskList<2, Task<Self>>,
+    tasks: TaskList<3, Task<Self>>,
 }
 
 impl<
@@ -86,7 +102,13 @@ impl<
         let db = Self {
             belt: EntityDb::new(&format!("{path}/belt"), revision)?,
             buckle: EntityDb::new(&format!("{path}/buckle"), buckle_revision)?,
-            tasks: TaskList::new([Self::work_belt, Self::work_buckle]),
+            lanes: lane::BeltLanes::new(&data_path)?,
+            sweeper: Sweeper::new(path)?,
+            tasks: TaskList::new([
+                Self::work_belt,
+                Self::work_buckle,
+                Self::work_sweep,
+            ]),
             ls: format!("<BeltDb {path} />"),
         };
 
@@ -101,6 +123,48 @@ impl<
         self.buckle.work()
     }
 
+    /// delete the belts of the deleted buckles, a page at a time
+    fn work_sweep(&mut self) -> Result<Performed, ShahError> {
+        if self.sweeper.work()?.0 {
+            return Ok(Performed(true));
+        }
+
+        let Some(mut sweep) = self.sweeper.current()? else {
+            return Ok(Performed(false));
+        };
+
+        let mut belt = [Bt::default()];
+        for _ in 0..PAGE_SIZE {
+            let found = self.belt.list(sweep.next.id, &mut belt).onf()?;
+            let belt = &mut belt[0];
+            // a belt that was deleted before a restart still links forward
+            if found.is_none()
+                || belt.gene() != &sweep.next
+                || belt.buckle() != &sweep.parent
+            {
+                sweep.next.clear();
+                break;
+            }
+
+            sweep.next = *belt.next();
+            if belt.entity_flags().is_alive() {
+                self.belt.del_unchecked(belt)?;
+                sweep.done += 1;
+            }
+            if sweep.next.is_none() {
+                break;
+            }
+        }
+
+        self.sweeper.update(&mut sweep)?;
+        Ok(Performed(true))
+    }
+
+    /// number of deleted buckles whose belts are still being deleted
+    pub fn sweep_pending(&self) -> usize {
+        self.sweeper.pending()
+    }
+
     // pub fn work(&mut self) -> Result<Performed, ShahError> {
     //     self.tasks.start();
     //     while let Some(task) = self.tasks.next() {
@@ -119,9 +183,9 @@ impl<
     BkO: Buckle,
     BtS,
     BkS,
-> Worker<2> for BeltDb<Bt, Bk, BtO, BkO, BtS, BkS>
+> Worker<3> for BeltDb<Bt, Bk, BtO, BkO, BtS, BkS>
 {
-    fn tasks(&mut self) -> &mut TaskList<2, Task<Self>> {
+    fn tasks(&mut self) -> &mut TaskList<3, Task<Self>> {
         &mut self.tasks
     }
 }
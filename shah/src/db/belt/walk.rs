@@ -0,0 +1,217 @@
+use super::*;
+use crate::{ClientError, Taker};
+use std::ops::{Bound, RangeBounds};
+
+/// where a walk over the belts of a buckle starts.
+/// a none anchor starts at the head, or the tail when walking backward
+#[crate::model]
+#[derive(Debug, PartialEq, Eq)]
+pub struct BeltWalk {
+    pub anchor: Gene,
+    pub backward: bool,
+    /// set by [`BeltDb::walk_range`] once there is nothing left to walk
+    pub done: bool,
+    _pad: [u8; 6],
+}
+
+impl BeltWalk {
+    pub const HEAD: Self =
+        Self { anchor: Gene::NONE, backward: false, done: false, _pad: [0; 6] };
+    pub const TAIL: Self = Self { backward: true, ..Self::HEAD };
+
+    /// start at the belt right after the anchor, following `next`
+    pub fn after(anchor: Gene) -> Self {
+        Self { anchor, ..Self::HEAD }
+    }
+
+    /// start at the belt right before the anchor, following `past`
+    pub fn before(anchor: Gene) -> Self {
+        Self { anchor, ..Self::TAIL }
+    }
+}
+
+impl<
+    Bt: Belt + EntityKochFrom<BtO, BtS>,
+    Bk: Buckle + EntityKochFrom<BkO, BkS>,
+    BtO: Belt,
+    BkO: Buckle,
+    BtS,
+    BkS,
+> BeltDb<Bt, Bk, BtO, BkO, BtS, BkS>
+{
+    /// the first belt of a walk. a sorted walk from the head or the tail
+    /// starts at the range, found through the lanes
+    fn walk_start(
+        &mut self, buckle_gene: &Gene, from: &BeltWalk,
+        range: &impl RangeBounds<u64>,
+    ) -> Result<Gene, ShahError> {
+        let mut buckle = Bk::default();
+        self.buckle.get(buckle_gene, &mut buckle)?;
+
+        if from.anchor.is_none() && from.backward {
+            // the last belt not greater than the end
+            let key = match range.end_bound() {
+                Bound::Included(max) => Some(*max),
+                Bound::Excluded(0) => return Ok(Gene::NONE),
+                Bound::Excluded(max) => Some(max - 1),
+                Bound::Unbounded => None,
+            };
+            return match key {
+                Some(key) => self.lane_find(&buckle, key),
+                None => Ok(*buckle.tail()),
+            };
+        }
+
+        if from.anchor.is_none() {
+            // right after the last belt less than the start
+            let key = match range.start_bound() {
+                Bound::Included(0) | Bound::Unbounded => None,
+                Bound::Included(min) => Some(min - 1),
+                Bound::Excluded(min) => Some(*min),
+            };
+            let Some(key) = key else { return Ok(*buckle.head()) };
+            let gene = self.lane_find(&buckle, key)?;
+            if gene.is_none() {
+                return Ok(*buckle.head());
+            }
+
+            let mut belt = Bt::default();
+            self.belt.get(&gene, &mut belt)?;
+            return Ok(*belt.next());
+        }
+
+        let mut anchor = Bt::default();
+        self.belt.get(&from.anchor, &mut anchor)?;
+        if anchor.buckle() != buckle.gene() {
+            log::warn!("{} walk: anchor is not in the buckle", self.ls);
+            return Err(SystemError::BeltBuckleMismatch)?;
+        }
+
+        Ok(if from.backward { *anchor.past() } else { *anchor.next() })
+    }
+
+    /// fill `out` with the belts of the buckle in chain order.
+    /// returns the number of belts read, the rest of `out` is zeroed.
+    /// continue with [`BeltWalk::after`] or [`BeltWalk::before`]
+    /// of the last belt when the page was full
+    pub fn walk(
+        &mut self, buckle_gene: &Gene, from: &BeltWalk, out: &mut [Bt],
+    ) -> Result<usize, ShahError> {
+        let mut from = *from;
+        self.walk_range(buckle_gene, &mut from, .., out)
+    }
+
+    /// same as [`Self::walk`] but only the belts with an order in `range`,
+    /// for unsorted belts the range must be `..`. a walk from the head or
+    /// the tail starts at the range. from an anchor the belts before the
+    /// range are skipped, at most [`BLOCK_SIZE`] of them per call, so a
+    /// call may return fewer belts than `out` can hold. `from` is moved
+    /// to the last belt that was read and is `done` once the chain or
+    /// the range has ended
+    pub fn walk_range(
+        &mut self, buckle_gene: &Gene, from: &mut BeltWalk,
+        range: impl RangeBounds<u64>, out: &mut [Bt],
+    ) -> Result<usize, ShahError> {
+        let bounded = !matches!(
+            (range.start_bound(), range.end_bound()),
+            (Bound::Unbounded, Bound::Unbounded)
+        );
+        if bounded && Bt::default().order().is_none() {
+            return Err(SystemError::BeltNotSorted)?;
+        }
+
+        let mut count = 0usize;
+        if from.done {
+            out.iter_mut().for_each(|b| b.zeroed());
+            return Ok(count);
+        }
+
+        let mut gene = self.walk_start(buckle_gene, from, &range)?;
+        let mut skipped = 0usize;
+        while count < out.len() && skipped < BLOCK_SIZE {
+            let belt = &mut out[count];
+            if gene.is_none() || self.belt.get(&gene, belt).onf()?.is_none() {
+                from.done = true;
+                break;
+            }
+            from.anchor = gene;
+            gene = if from.backward { *belt.past() } else { *belt.next() };
+
+            let Some(key) = belt.order().filter(|_| bounded) else {
+                count += 1;
+                continue;
+            };
+            if range.contains(&key) {
+                count += 1;
+                continue;
+            }
+            let before = match range.start_bound() {
+                Bound::Included(min) => key < *min,
+                Bound::Excluded(min) => key <= *min,
+                Bound::Unbounded => false,
+            };
+            // past the end of the range in the walk direction
+            if before == from.backward {
+                from.done = true;
+                break;
+            }
+            skipped += 1;
+        }
+
+        if gene.is_none() {
+            from.done = true;
+        }
+        out[count..].iter_mut().for_each(|b| b.zeroed());
+
+        Ok(count)
+    }
+}
+
+type C<Ok, E> = Result<Ok, ClientError<E>>;
+/// client side walk over a buckle, one request per page of `N` belts
+pub struct BeltClient<
+    Bt: Belt,
+    E: IsNotFound + From<u16> + Copy,
+    const N: usize,
+> {
+    pub walk: fn(&Taker, &Gene, &BeltWalk) -> C<[Bt; N], E>,
+}
+
+impl<Bt: Belt, E: IsNotFound + From<u16> + Copy, const N: usize>
+    BeltClient<Bt, E, N>
+{
+    /// call `each` for every belt from `from` to the end of the chain
+    pub fn walk(
+        &self, taker: &Taker, buckle_gene: &Gene, from: BeltWalk,
+        mut each: impl FnMut(&Bt),
+    ) -> Result<(), ClientError<E>> {
+        buckle_gene.validate()?;
+
+        let mut from = from;
+        loop {
+            let page = (self.walk)(taker, buckle_gene, &from)?;
+            let mut last = None;
+            for belt in page.iter().take_while(|b| b.gene().is_some()) {
+                each(belt);
+                last = Some(*belt.gene());
+            }
+
+            let full = page.last().is_some_and(|b| b.gene().is_some());
+            match last {
+                Some(gene) if full => from.anchor = gene,
+                _ => break,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// collect every belt of the buckle from head to tail
+    pub fn all(
+        &self, taker: &Taker, buckle_gene: &Gene,
+    ) -> Result<Vec<Bt>, ClientError<E>> {
+        let mut out = Vec::new();
+        self.walk(taker, buckle_gene, BeltWalk::HEAD, |b| out.push(*b))?;
+        Ok(out)
+    }
+}
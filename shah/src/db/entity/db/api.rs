@@ -35,6 +35,7 @@ impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
                 self.set_unchecked(&mut oldie)?;
                 if !oldie.entity_flags().is_alive() {
                     self.dead_add(oldie.gene());
+                    self.meta_set()?;
                     return Err(NotFound::EntityNotAlive)?;
                 }
                 entity.clone_from(&oldie);
@@ -66,14 +67,26 @@ impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
 
         *entity.growth_mut() = 0;
         self.set_unchecked(entity)?;
-        self.live += 1;
+        self.meta.live += 1;
+        self.meta_set()?;
 
         Ok(())
     }
 
+    /// number of alive records
+    pub fn live(&self) -> GeneId {
+        self.meta.live
+    }
+
     pub fn count(&mut self) -> Result<EntityCount, ShahError> {
         let (total, size) = self.total()?;
-        Ok(EntityCount { total, alive: self.live, size })
+        Ok(EntityCount {
+            total,
+            alive: self.meta.live,
+            dead: self.meta.dead,
+            exhausted: self.meta.exhausted,
+            size,
+        })
     }
 
     pub fn set(&mut self, entity: &mut T) -> Result<(), ShahError> {
@@ -98,14 +111,71 @@ impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
         Ok(())
     }
 
+    /// apply the fields of the patch to the stored entity.
+    /// the patched entity is written back into `entity`
+    pub fn patch(
+        &mut self, patch: &EntityPatch, entity: &mut T,
+    ) -> Result<(), ShahError> {
+        self.get(&patch.gene, entity)?;
+
+        let base = entity as *const T as usize;
+        let growth = entity.growth_mut() as *const u64 as usize;
+        let protected = [
+            (entity.gene() as *const Gene as usize - base, Gene::S),
+            (growth - base, u64::S),
+            (entity.entity_flags() as *const _ as usize - base, 1),
+        ];
+
+        let schema = T::shah_schema();
+        let mut patched = *entity;
+        let buf = patched.as_binary_mut();
+        for (path, data) in patch.iter() {
+            let Some((offset, kind)) = schema.resolve(path) else {
+                log::warn!("{} patch: unknown path: {path:?}", self.ls);
+                return Err(SystemError::BadPatchPath)?;
+            };
+            let size = kind.size();
+            let Some(data) = data.filter(|d| d.len() == size) else {
+                return Err(SystemError::BadPatchLength)?;
+            };
+            if protected
+                .iter()
+                .any(|(p, s)| offset < p + s && *p < offset + size)
+            {
+                return Err(SystemError::PatchProtected)?;
+            }
+
+            buf[offset..offset + size].copy_from_slice(data);
+        }
+
+        self.set_unchecked(&mut patched)?;
+        entity.clone_from(&patched);
+
+        Ok(())
+    }
+
     pub fn keyed(
         &mut self, key: &Gene, entity: &mut T,
     ) -> Result<(), ShahError> {
         if self.get(key, entity).onf()?.is_none() {
+            let old = self.read_at(entity, key.id).onf()?.map(|_| {
+                (entity.gene().id != 0, entity.entity_flags().is_alive())
+            });
+
             entity.zeroed();
             entity.entity_flags_mut().set_is_alive(true);
             *entity.gene_mut() = *key;
             self.set_unchecked(entity)?;
+
+            match old {
+                Some((true, true)) => {}
+                Some((true, false)) => {
+                    self.meta.dead -= 1;
+                    self.meta.live += 1;
+                }
+                _ => self.meta.live += 1,
+            }
+            self.meta_set()?;
         }
 
         Ok(())
@@ -169,6 +239,7 @@ impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
                 self.set_unchecked(&mut old)?;
                 if !old.entity_flags().is_alive() {
                     self.dead_add(old.gene());
+                    self.meta_set()?;
                 }
                 item.clone_from(&old);
             }
@@ -176,4 +247,51 @@ impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
 
         Ok(count)
     }
+
+    /// find the alive items matching the filter expression.
+    /// scanning starts at `cursor` and at most [`BLOCK_SIZE`] items are
+    /// read per call. `cursor` is set to where the next call should
+    /// continue, or zero when the end of the db was reached
+    pub fn scan_where(
+        &mut self, expr: &str, cursor: &mut GeneId, out: &mut [T],
+    ) -> Result<usize, ShahError> {
+        let filter = Filter::new(&T::shah_schema(), expr)?;
+
+        if *cursor == 0 {
+            *cursor = GeneId(1);
+        }
+
+        let mut buf = [T::default(); PAGE_SIZE];
+        let mut count = 0usize;
+        let mut scanned = 0usize;
+        while count < out.len() && scanned < BLOCK_SIZE {
+            let Some(len) = self.list(*cursor, &mut buf).onf()? else {
+                *cursor = GeneId(0);
+                break;
+            };
+
+            for (idx, item) in buf[..len].iter().enumerate() {
+                if count >= out.len() {
+                    *cursor += idx as u64;
+                    return Ok(count);
+                }
+                if item.gene().id != 0
+                    && item.entity_flags().is_alive()
+                    && filter.test(item.as_binary())
+                {
+                    out[count] = *item;
+                    count += 1;
+                }
+            }
+
+            scanned += len;
+            *cursor += len as u64;
+            if len < PAGE_SIZE {
+                *cursor = GeneId(0);
+                break;
+            }
+        }
+
+        Ok(count)
+    }
 }
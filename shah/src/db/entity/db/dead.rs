@@ -10,17 +10,191 @@ impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
         self.dead_list.pop(|_| true).unwrap_or_default()
     }
 
-    pub(super) fn dead_add(&mut self, gene: &Gene) {
+    /// count a newly dead record and keep its id for reuse.
+    /// the counters are not written to the disk
+    pub(crate) fn dead_add(&mut self, gene: &Gene) {
         if gene.id == 0 {
             return;
         }
 
-        self.live -= 1;
+        self.meta.live -= 1;
+        self.meta.dead += 1;
 
-        if gene.exhausted() || self.dead_list.disabled() {
+        if self.recycle.exhausted(gene) && self.recycle.quarantine().is_none() {
+            self.meta.exhausted += 1;
+        }
+
+        self.dead_keep(gene);
+    }
+
+    /// keep the id of a dead record for reuse, without counting it
+    pub(super) fn dead_keep(&mut self, gene: &Gene) {
+        if gene.id == 0 {
+            return;
+        }
+
+        if self.recycle.exhausted(gene) {
+            let Some(dur) = self.recycle.quarantine() else { return };
+            if self.quarantine.len() < BLOCK_SIZE {
+                self.quarantine.push_back((Instant::now() + dur, gene.id));
+            } else {
+                self.quarantine_lost += 1;
+            }
+            return;
+        }
+
+        if self.dead_list.disabled() {
             return;
         }
 
+        if self.dead_list.is_full() {
+            self.dead_lost += 1;
+            return;
+        }
         self.dead_list.push(gene.id);
     }
+
+    /// pop an id that has finished its quarantine
+    pub(crate) fn quarantine_pop(&mut self) -> Option<GeneId> {
+        let (until, id) = *self.quarantine.front()?;
+        if until > Instant::now() {
+            return None;
+        }
+        self.quarantine.pop_front();
+        Some(id)
+    }
+
+    /// find the ids that did not fit in the dead list or the quarantine
+    /// again, once one of them is empty. lost exhausted ids get a full
+    /// quarantine of their own
+    pub(super) fn work_dead_scan(&mut self) -> Result<Performed, ShahError> {
+        if self.dead_scan.ended() {
+            let dead = self.dead_lost > 0 && self.dead_list.is_empty();
+            let quarantine =
+                self.quarantine_lost > 0 && self.quarantine.is_empty();
+            if !dead && !quarantine {
+                return Ok(Performed(false));
+            }
+
+            let (total, _) = self.total()?;
+            log::info!(
+                "{} looking for {} lost dead ids and {} lost quarantine ids",
+                self.ls,
+                self.dead_lost,
+                self.quarantine_lost
+            );
+            self.dead_lost = 0;
+            self.quarantine_lost = 0;
+            self.dead_scan.prog = GeneId(1);
+            self.dead_scan.total = total + 1;
+        }
+
+        let mut entity = T::default();
+        for _ in 0..self.work_iter {
+            let Some(id) = self.dead_scan.next() else { break };
+            if self.read_at(&mut entity, id).onf()?.is_none() {
+                self.dead_scan.end();
+                break;
+            }
+
+            let gene = entity.gene();
+            if entity.entity_flags().is_alive()
+                || self.quarantine.iter().any(|(_, qid)| *qid == gene.id)
+            {
+                continue;
+            }
+            // the dead list does not take an id twice
+            self.dead_keep(gene);
+        }
+
+        Ok(Performed(true))
+    }
+
+    /// restore the dead ids of a clean shutdown. after an unclean one
+    /// the setup scan finds them again
+    pub(super) fn dead_load(&mut self) -> Result<(), ShahError> {
+        let buf = match std::fs::read(&self.dead_path) {
+            Ok(buf) => buf,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                // closed cleanly by an older version that did not save them
+                if !self.recount && self.meta.dead > self.meta.exhausted {
+                    self.dead_lost = self.meta.dead.0 - self.meta.exhausted.0;
+                }
+                return Ok(());
+            }
+            Err(e) => return Err(e)?,
+        };
+        std::fs::remove_file(&self.dead_path)?;
+        if self.recount {
+            return Ok(());
+        }
+
+        if self.dead_parse(&buf).is_none() {
+            log::warn!("{} dead ids file is cut short", self.ls);
+            self.dead_lost += 1;
+            self.quarantine_lost += 1;
+        }
+
+        Ok(())
+    }
+
+    fn dead_parse(&mut self, buf: &[u8]) -> Option<()> {
+        let mut words = buf
+            .chunks_exact(8)
+            .map(|w| u64::from_le_bytes(w.try_into().unwrap()));
+
+        self.dead_lost = words.next()?;
+        self.quarantine_lost = words.next()?;
+
+        for _ in 0..words.next()? {
+            let id = GeneId(words.next()?);
+            if self.dead_list.disabled() {
+                continue;
+            }
+            if self.dead_list.is_full() {
+                self.dead_lost += 1;
+                continue;
+            }
+            self.dead_list.push(id);
+        }
+
+        let now = Instant::now();
+        let unix = utils::now_ms();
+        for _ in 0..words.next()? {
+            let (until, id) = (words.next()?, GeneId(words.next()?));
+            let left = Duration::from_millis(until.saturating_sub(unix));
+            self.quarantine.push_back((now + left, id));
+        }
+
+        Some(())
+    }
+
+    /// reset the iter of the dead ids that finished their quarantine and
+    /// put them back into the dead list.
+    /// with a disabled dead list the owner of this db must do this
+    pub(super) fn work_quarantine(&mut self) -> Result<Performed, ShahError> {
+        if self.dead_list.disabled() || self.dead_list.is_full() {
+            return Ok(Performed(false));
+        }
+
+        let mut entity = T::default();
+        let mut performed = false;
+        for _ in 0..self.work_iter {
+            let Some(id) = self.quarantine_pop() else { break };
+            performed = true;
+
+            if self.read_at(&mut entity, id).onf()?.is_none()
+                || entity.entity_flags().is_alive()
+                || !entity.gene().exhausted()
+            {
+                continue;
+            }
+
+            entity.gene_mut().iter = 0;
+            self.set_unchecked(&mut entity)?;
+            self.dead_list.push(id);
+        }
+
+        Ok(Performed(performed))
+    }
 }
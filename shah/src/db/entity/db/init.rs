@@ -23,15 +23,27 @@ impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
             .open(data_path.join(format!("{name}.{revision}.shah")))?;
 
         let mut db = Self {
-            live: GeneId(0),
+            meta: EntityMeta::default(),
+            recount: true,
             dead_list: DeadList::<GeneId, BLOCK_SIZE>::new(),
+            recycle: GeneRecycle::Never,
+            quarantine: VecDeque::new(),
+            dead_lost: 0,
+            quarantine_lost: 0,
+            dead_scan: Default::default(),
+            dead_path: data_path.join(format!("{name}.{revision}.dead")),
             file,
             revision,
             name: name.to_string(),
             koch: None,
             koch_prog: Default::default(),
             setup_prog: Default::default(),
-            tasks: TaskList::new([Self::work_koch, Self::work_setup_task]),
+            tasks: TaskList::new([
+                Self::work_koch,
+                Self::work_setup_task,
+                Self::work_quarantine,
+                Self::work_dead_scan,
+            ]),
             ls: format!("<EntityDb {path}.{revision} />"),
             inspector: None,
             work_iter: 10,
@@ -47,10 +59,25 @@ impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
         self.init_head()?;
         self.koch_prog_get()?;
 
-        self.live = GeneId(0);
+        self.recount = !self.meta.flags.is_clean();
+        if self.recount {
+            log::info!("{} was not closed cleanly", self.ls);
+            self.meta.live = GeneId(0);
+            self.meta.dead = GeneId(0);
+            self.meta.exhausted = GeneId(0);
+        }
+
+        // until we are closed cleanly the counters on the disk are suspect
+        self.meta.flags.set_is_clean(false);
+        self.meta_set()?;
+
+        self.quarantine.clear();
         if !self.dead_list.disabled() {
             self.dead_list.clear();
         }
+        self.dead_lost = 0;
+        self.quarantine_lost = 0;
+        self.dead_load()?;
 
         let file_size = self.file_size()?;
         if file_size < ENTITY_META {
@@ -67,15 +94,32 @@ impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
             return Ok(());
         }
 
-        self.live = GeneId(((file_size - ENTITY_META) / T::N) - 1);
+        let total = GeneId(((file_size - ENTITY_META) / T::N) - 1);
+        if self.recount {
+            self.meta.live = total;
+        }
 
-        self.setup_prog.prog = GeneId(1);
-        self.setup_prog.total = self.live + 1;
-        log::info!("{} init::setup_task {:?}", self.ls, self.setup_prog);
+        self.setup_prog.total = total + 1;
+        self.setup_start();
 
         Ok(())
     }
 
+    /// go over the records that are already in the file. this counts them
+    /// after an unclean shutdown and shows them to the inspector.
+    /// after a clean one the counters and the dead ids are already known
+    pub(super) fn setup_start(&mut self) {
+        if self.setup_prog.total <= GeneId(1)
+            || (!self.recount && self.inspector.is_none())
+        {
+            self.setup_prog.end();
+            return;
+        }
+
+        self.setup_prog.prog = GeneId(1);
+        log::info!("{} init::setup_task {:?}", self.ls, self.setup_prog);
+    }
+
     fn init_head(&mut self) -> Result<(), ShahError> {
         let mut head = EntityHead::default();
         if let Err(e) = self.file.read_exact_at(head.as_binary_mut(), 0) {
@@ -94,7 +138,16 @@ impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
             head.item_size = T::N;
 
             let svec = T::shah_schema().encode();
-            head.schema[0..svec.len()].clone_from_slice(&svec);
+            let Some(schema) = head.schema.get_mut(..svec.len()) else {
+                log::error!(
+                    "{} schema is {} bytes, the head only has room for {}",
+                    self.ls,
+                    svec.len(),
+                    head.schema.len()
+                );
+                return Err(DbError::SchemaTooBig)?;
+            };
+            schema.clone_from_slice(&svec);
 
             self.file.write_all_at(head.as_binary(), 0)?;
 
@@ -102,6 +155,7 @@ impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
         }
 
         head.check::<T>(self.revision, &self.ls)?;
+        self.meta = head.meta;
 
         Ok(())
     }
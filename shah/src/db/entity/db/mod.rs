@@ -1,10 +1,13 @@
 use std::cell::{RefCell, RefMut};
+use std::collections::VecDeque;
 use std::marker::PhantomData;
 use std::ops::AddAssign;
+use std::path::PathBuf;
 use std::{
     fs::File,
     io::{ErrorKind, Seek, SeekFrom},
     os::unix::fs::FileExt,
+    time::{Duration, Instant},
 };
 
 use super::*;
@@ -45,16 +48,87 @@ pub struct EntityDb<
     Is = (),
 > {
     file: File,
-    pub live: GeneId,
+    meta: EntityMeta,
+    /// the last shutdown was not clean and setup must count the records
+    /// and collect the dead ids
+    recount: bool,
     dead_list: DeadList<GeneId, BLOCK_SIZE>,
+    recycle: GeneRecycle,
+    /// exhausted ids waiting for [`GeneRecycle::Rotate`]
+    quarantine: VecDeque<(Instant, GeneId)>,
+    /// dead ids that did not fit in the dead list
+    dead_lost: u64,
+    /// exhausted ids that did not fit in the quarantine
+    quarantine_lost: u64,
+    /// looks for the lost ids once there is room for them
+    dead_scan: ShahProgress,
+    /// the dead ids and the quarantine are kept here over a clean restart
+    dead_path: PathBuf,
     revision: u16,
     name: String,
     koch: Option<EntityKoch<T, O, S>>,
     koch_prog: ShahProgress,
     setup_prog: ShahProgress,
-    tasks: TaskList<2, Task<Self>>,
+    tasks: TaskList<4, Task<Self>>,
     ls: String,
     inspector: Option<EntityInspector<T, Is>>,
     work_iter: usize,
     // cache: HashMap<Gene, T>,
 }
+
+impl<T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, S, Is>
+    EntityDb<T, O, S, Is>
+{
+    /// write the dead ids and the quarantine next to the db. the time left
+    /// of the quarantine is kept as a unix time, so it keeps running while
+    /// the db is closed
+    fn dead_save(&mut self) -> std::io::Result<()> {
+        let mut dead = Vec::with_capacity(self.dead_list.len());
+        if !self.dead_list.disabled() {
+            while let Some(id) = self.dead_list.pop(|_| true) {
+                dead.push(id);
+            }
+        }
+
+        let now = Instant::now();
+        let unix = utils::now_ms();
+        let size = 32 + self.quarantine.len() * 16 + dead.len() * 8;
+        let mut buf = Vec::with_capacity(size);
+        buf.extend_from_slice(&self.dead_lost.to_le_bytes());
+        buf.extend_from_slice(&self.quarantine_lost.to_le_bytes());
+        buf.extend_from_slice(&(dead.len() as u64).to_le_bytes());
+        for id in dead.iter() {
+            buf.extend_from_slice(&id.0.to_le_bytes());
+        }
+        buf.extend_from_slice(&(self.quarantine.len() as u64).to_le_bytes());
+        for (until, id) in self.quarantine.iter() {
+            let left = until.saturating_duration_since(now).as_millis() as u64;
+            buf.extend_from_slice(&(unix + left).to_le_bytes());
+            buf.extend_from_slice(&id.0.to_le_bytes());
+        }
+
+        std::fs::write(&self.dead_path, buf)
+    }
+}
+
+impl<T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, S, Is> Drop
+    for EntityDb<T, O, S, Is>
+{
+    fn drop(&mut self) {
+        if self.recount && !self.setup_prog.ended() {
+            log::warn!("{} closed before counting was done", self.ls);
+            return;
+        }
+
+        if let Err(e) = self.dead_save() {
+            log::error!("{} could not save the dead ids: {e:?}", self.ls);
+            return;
+        }
+
+        self.meta.flags.set_is_clean(true);
+        let meta = self.meta.as_binary();
+        if let Err(e) = self.file.write_all_at(meta, ENTITY_META_POS) {
+            log::error!("{} could not mark as clean: {e:?}", self.ls);
+        }
+    }
+}
@@ -11,11 +11,15 @@ impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
 
         if !self.koch_prog.ended() {
             self.setup_prog.total = self.koch_prog.prog;
-            self.setup_prog.prog = GeneId(1);
+            self.setup_start();
         }
 
-        if self.live < koch.total {
-            self.live = GeneId(koch.total.0.saturating_sub(1));
+        // after a clean shutdown the counters already include the koch
+        if self.recount && self.meta.live < koch.total {
+            self.meta.live = GeneId(koch.total.0.saturating_sub(1));
+        }
+
+        if self.file_size()? < ENTITY_META + (koch.total * T::N).0 {
             utils::falloc(&self.file, ENTITY_META, (koch.total * T::N).0)?;
         }
 
@@ -26,12 +30,29 @@ impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
 
     pub fn set_inspector(&mut self, inspector: EntityInspector<T, Is>) {
         self.inspector = Some(inspector);
+        if self.setup_prog.ended() {
+            self.setup_start();
+        }
     }
 
     pub fn set_dead_list_disabled(&mut self, disabled: bool) {
         self.dead_list.disable(disabled)
     }
 
+    pub fn set_gene_recycle(&mut self, recycle: GeneRecycle) {
+        self.recycle = recycle;
+        if recycle.quarantine().is_none() {
+            self.meta.exhausted += self.quarantine.len() as u64;
+            self.meta.exhausted += self.quarantine_lost;
+            self.quarantine.clear();
+            self.quarantine_lost = 0;
+        }
+    }
+
+    pub fn gene_recycle(&self) -> GeneRecycle {
+        self.recycle
+    }
+
     pub fn set_work_iter(&mut self, work_iter: usize) {
         self.work_iter = work_iter.clamp(5, 100);
     }
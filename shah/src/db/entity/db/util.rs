@@ -3,6 +3,20 @@ use super::*;
 impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
     EntityDb<T, O, S, Is>
 {
+    pub(crate) fn meta_set(&mut self) -> Result<(), ShahError> {
+        self.file.write_all_at(self.meta.as_binary(), ENTITY_META_POS)?;
+        Ok(())
+    }
+
+    /// change the counters from outside of the entity db.
+    /// e.g. pond stacks that add many dead records at once
+    pub(crate) fn meta_update<F: FnOnce(&mut EntityMeta)>(
+        &mut self, f: F,
+    ) -> Result<(), ShahError> {
+        f(&mut self.meta);
+        self.meta_set()
+    }
+
     pub(crate) fn file_size(&mut self) -> std::io::Result<u64> {
         self.file.seek(SeekFrom::End(0))
     }
@@ -125,7 +139,7 @@ impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
         entity.entity_flags_mut().set_is_alive(false);
         self.set_unchecked(entity)?;
         self.dead_add(entity.gene());
-        Ok(())
+        self.meta_set()
     }
 
     pub(crate) fn set_unchecked(
@@ -158,19 +172,18 @@ impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
         let mut gene = Gene { id: self.take_dead_id(), ..Default::default() };
         utils::getrandom(&mut gene.pepper);
         gene.server = ShahConfig::get().server;
-        gene.iter = 0;
 
         if gene.id != 0 {
             let mut old = T::default();
-            if self.read_at(&mut old, gene.id).is_ok() {
-                let og = old.gene();
-                if !og.exhausted() {
-                    gene.iter = og.iter + 1;
-                    return Ok(gene);
-                }
+            if self.read_at(&mut old, gene.id).is_ok()
+                && self.recycle.next(old.gene(), &mut gene)
+            {
+                self.meta.dead -= 1;
+                return Ok(gene);
             }
         }
 
+        self.recycle.first(&mut gene);
         gene.id = self.new_gene_id()?;
 
         Ok(gene)
@@ -3,12 +3,13 @@ use super::*;
 impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
     EntityDb<T, O, S, Is>
 {
-    pub(super) fn inspection(&mut self, entity: &T) {
+    /// `count` is false when the record is already in the counters.
+    /// after a clean shutdown the dead ids come from the dead ids file
+    pub(super) fn inspection(&mut self, entity: &T, count: bool) {
         // log::debug!("\x1b[36minspecting\x1b[m: {:?}", entity.gene());
 
-        if !entity.entity_flags().is_alive() {
-            let gene = entity.gene();
-            self.dead_add(gene);
+        if count && !entity.entity_flags().is_alive() {
+            self.dead_add(entity.gene());
             // log::debug!("{} found dead: {} | {}", self.ls, gene.id, self.live);
         }
 
@@ -39,7 +40,13 @@ impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
                 break;
             }
 
-            self.inspection(&entity);
+            self.inspection(&entity, self.recount);
+        }
+
+        if self.recount && self.setup_prog.ended() {
+            self.recount = false;
+            self.meta_set()?;
+            log::info!("{} counted: {:?}", self.ls, self.meta);
         }
 
         Ok(Performed(performed))
@@ -100,12 +107,13 @@ impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
             }
 
             self.write_buf_at(&old, id)?;
-            self.inspection(&old);
+            self.inspection(&old, true);
             log::debug!("{} koched: {:?}", self.ls, old.gene());
         }
 
         if performed {
             self.koch_prog_set()?;
+            self.meta_set()?;
         }
 
         Ok(Performed(performed))
@@ -113,9 +121,9 @@ impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
 }
 
 impl<S, T: EntityItem + EntityKochFrom<O, S>, O: EntityItem, Is: 'static>
-    Worker<2> for EntityDb<T, O, S, Is>
+    Worker<4> for EntityDb<T, O, S, Is>
 {
-    fn tasks(&mut self) -> &mut TaskList<2, Task<Self>> {
+    fn tasks(&mut self) -> &mut TaskList<4, Task<Self>> {
         &mut self.tasks
     }
 }
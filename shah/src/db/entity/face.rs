@@ -14,6 +14,8 @@ use crate::{
 #[cfg_attr(not(feature = "serde"), shah::flags(inner = u8, serde = false))]
 pub struct EntityFlags {
     pub is_alive: bool,
+    /// deleted but its children are still being swept
+    pub is_dying: bool,
 }
 
 pub trait Entity: ShahModel {
@@ -1,6 +1,6 @@
 use super::EntityItem;
 use crate::models::{
-    Binary, DbHead, Schema, ShahMagic, ShahMagicDb, ShahProgress,
+    Binary, DbHead, GeneId, Schema, ShahMagic, ShahMagicDb, ShahProgress,
 };
 use crate::{DbError, ShahError};
 
@@ -8,13 +8,36 @@ pub const ENTITY_META: u64 = EntityHead::N + ShahProgress::N;
 pub const ENTITY_VERSION: u16 = 1;
 pub const ENTITY_MAGIC: ShahMagic =
     ShahMagic::new_const(ShahMagicDb::Entity as u16);
+pub(crate) const ENTITY_META_POS: u64 =
+    core::mem::offset_of!(EntityHead, meta) as u64;
+
+#[cfg_attr(feature = "serde", shah::flags(inner = u8, serde = true))]
+#[cfg_attr(not(feature = "serde"), shah::flags(inner = u8, serde = false))]
+pub struct EntityMetaFlags {
+    /// the db was closed cleanly and the counters can be trusted
+    pub is_clean: bool,
+}
+
+/// counters of the db. they are kept up to date on every add and del.
+/// older files have this zeroed, which reads as an unclean shutdown
+#[crate::model]
+#[derive(Debug)]
+pub struct EntityMeta {
+    pub live: GeneId,
+    pub dead: GeneId,
+    /// dead ids that will never be reused
+    pub exhausted: GeneId,
+    pub flags: EntityMetaFlags,
+    _pad: [u8; 7],
+}
 
 #[crate::model]
 #[derive(Debug)]
 pub struct EntityHead {
     pub db_head: DbHead,
     pub item_size: u64,
-    pub schema: [u8; 4096],
+    pub schema: [u8; 4096 - EntityMeta::S],
+    pub meta: EntityMeta,
 }
 
 impl EntityHead {
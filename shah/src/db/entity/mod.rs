@@ -4,15 +4,20 @@ mod db;
 mod face;
 mod koch;
 mod meta;
+mod patch;
 
 pub use db::*;
 pub use face::*;
 pub use koch::*;
 pub use meta::*;
+pub use patch::*;
 
 #[derive(Debug)]
 pub struct EntityCount {
     pub alive: GeneId,
     pub total: GeneId,
+    pub dead: GeneId,
+    /// dead ids that will never be reused. see [`crate::models::GeneRecycle`]
+    pub exhausted: GeneId,
     pub size: u64,
 }
@@ -0,0 +1,79 @@
+use crate::models::{Binary, Gene, ShahString};
+use crate::{ShahError, SystemError};
+
+pub const PATCH_FIELDS: usize = 16;
+pub const PATCH_DATA: usize = 2048;
+
+#[crate::model]
+#[derive(Debug)]
+pub struct PatchField {
+    /// path of the field in the schema. e.g. `sessions.1.info.os`
+    pub path: ShahString<56>,
+    /// start of the value in [`EntityPatch::data`]
+    pub start: u32,
+    pub len: u32,
+}
+
+/// a partial update of an entity.
+/// each field is resolved against the schema of the entity and only
+/// those bytes are written. see [`super::EntityDb::patch`]
+#[crate::model]
+#[derive(Debug)]
+pub struct EntityPatch {
+    pub gene: Gene,
+    pub fields: [PatchField; PATCH_FIELDS],
+    pub data: [u8; PATCH_DATA],
+    pub count: u16,
+    _pad: [u8; 6],
+}
+
+impl EntityPatch {
+    pub fn new(gene: &Gene) -> Self {
+        Self { gene: *gene, ..Default::default() }
+    }
+
+    pub fn set<V: Binary>(
+        &mut self, path: &str, value: &V,
+    ) -> Result<&mut Self, ShahError> {
+        self.set_bytes(path, value.as_binary())
+    }
+
+    pub fn set_bytes(
+        &mut self, path: &str, value: &[u8],
+    ) -> Result<&mut Self, ShahError> {
+        let count = self.count as usize;
+        if count >= PATCH_FIELDS {
+            return Err(SystemError::BadPatchLength)?;
+        }
+
+        let start = match count {
+            0 => 0,
+            c => {
+                let last = &self.fields[c - 1];
+                (last.start + last.len) as usize
+            }
+        };
+        if start + value.len() > PATCH_DATA {
+            return Err(SystemError::BadPatchLength)?;
+        }
+
+        let field = &mut self.fields[count];
+        if field.path.set(path) {
+            return Err(SystemError::BadPatchPath)?;
+        }
+        field.start = start as u32;
+        field.len = value.len() as u32;
+
+        self.data[start..start + value.len()].copy_from_slice(value);
+        self.count += 1;
+        Ok(self)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, Option<&[u8]>)> {
+        let count = (self.count as usize).min(PATCH_FIELDS);
+        self.fields[..count].iter().map(|f| {
+            let (start, len) = (f.start as usize, f.len as usize);
+            (f.path.as_str(), self.data.get(start..start + len))
+        })
+    }
+}
@@ -3,6 +3,7 @@ pub mod belt;
 pub mod entity;
 pub mod pond;
 pub mod snake;
+pub(crate) mod sweep;
 pub mod trie;
 pub mod trie_const;
 
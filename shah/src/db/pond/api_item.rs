@@ -1,21 +1,23 @@
 use super::*;
 use crate::ShahError;
-use crate::config::ShahConfig;
+use crate::db::derr;
 use crate::db::entity::{EntityCount, EntityKochFrom};
 use crate::models::{Gene, GeneId};
-use crate::{PAGE_SIZE, SystemError};
+use crate::{OptNotFound, SystemError};
+use std::ops::{Bound, RangeBounds};
 
 impl<
     Dk: Duck + EntityKochFrom<DkO, DkS>,
     Pn: Pond + EntityKochFrom<PnO, PnS>,
     Og: Origin + EntityKochFrom<OgO, OgS>,
     DkO: Duck,
-    PnO: Pond,
+    PnO: EntityItem,
     OgO: Origin,
     DkS,
     PnS,
     OgS,
-> PondDb<Dk, Pn, Og, DkO, PnO, OgO, DkS, PnS, OgS>
+    const STACK: usize,
+> PondDb<Dk, Pn, Og, DkO, PnO, OgO, DkS, PnS, OgS, STACK>
 {
     pub fn add(
         &mut self, origene: &Gene, item: &mut Dk,
@@ -24,69 +26,24 @@ impl<
 
         let mut origin = Og::default();
         self.origin.get(origene, &mut origin)?;
+        if origin.entity_flags().is_dying() {
+            return derr!(self.ls, SystemError::OriginDying);
+        }
         *origin.item_count_mut() += 1;
 
-        let mut pond = self.half_empty_pond(&mut origin)?;
-        *pond.alive_mut() += 1;
-
-        let mut buf = [Dk::default(); PAGE_SIZE];
-        *item.pond_mut() = *pond.gene();
-        *item.growth_mut() = 0;
-        let ig = item.gene_mut();
-        ig.server = ShahConfig::get().server;
-        crate::utils::getrandom(&mut ig.pepper);
-
-        let stack = if pond.stack() == 0 {
-            let stack = self.new_stack_id()?;
-            for (idx, x) in buf.iter_mut().enumerate() {
-                let xg = x.gene_mut();
-                xg.id = stack + idx as u64;
-                xg.server = ig.server;
-                *x.pond_mut() = *pond.gene();
+        let mut pond = match item.order() {
+            Some(order) => {
+                let mut pond = self.sorted_pond(&mut origin, order)?;
+                Self::pond_order(&mut pond, order);
+                pond
             }
-            ig.id = stack;
-            ig.iter = 0;
-            buf[0] = *item;
-
-            *pond.stack_mut() = stack;
-            *pond.empty_mut() = PAGE_SIZE as u8 - 1;
-            stack
-        } else {
-            self.item.list(pond.stack(), &mut buf)?;
-
-            let mut found_empty_slot = false;
-            for (x, slot) in buf.iter_mut().enumerate() {
-                let sg = slot.gene();
-                if !slot.entity_flags().is_alive() && !sg.exhausted() {
-                    let ig = item.gene_mut();
-                    ig.id = pond.stack() + x as u64;
-                    if sg.id != 0 {
-                        ig.iter = sg.iter + 1;
-                        *item.growth_mut() = slot.growth() + 1;
-                    } else {
-                        ig.iter = 0;
-                    }
-                    slot.clone_from(item);
-                    found_empty_slot = true;
-                    *pond.empty_mut() = pond.empty().saturating_sub(1);
-                    // if pond.empty() > 0 {
-                    //     pond.empty -= 1;
-                    // }
-                    break;
-                }
-            }
-            if !found_empty_slot {
-                log::error!("could not found an empty slot for item");
-                return Err(SystemError::PondNoEmptySlotWasFound)?;
-            }
-
-            pond.stack()
+            None => self.half_empty_pond(&mut origin)?,
         };
+        *pond.alive_mut() += 1;
 
-        self.item.write_buf_at(&buf, stack)?;
+        self.pond_put(&mut pond, item)?;
         self.pond.set(&mut pond)?;
         self.origin.set(&mut origin)?;
-        self.item.live += 1;
 
         Ok(())
     }
@@ -112,6 +69,11 @@ impl<
 
         *entity.growth_mut() = old_entity.growth();
         *entity.pond_mut() = *old_entity.pond();
+        if let (Some(order), Some(new)) =
+            (old_entity.order(), entity.order_mut())
+        {
+            *new = order;
+        }
         self.item.set_unchecked(entity)?;
 
         Ok(())
@@ -137,7 +99,8 @@ impl<
         //     origin.items -= 1;
         // }
 
-        if pond.alive() == 0 {
+        // the ponds of a dying origin stay linked for the sweep
+        if pond.alive() == 0 && !origin.entity_flags().is_dying() {
             self.add_empty_pond(&mut origin, pond)?;
         } else {
             self.pond.set(&mut pond)?;
@@ -148,9 +111,103 @@ impl<
         Ok(())
     }
 
+    /// move the duck to a pond of another origin. ducks are placed by
+    /// their slot so it gets a new gene, which is written into `item`
+    pub fn change_origin(
+        &mut self, gene: &Gene, new_origene: &Gene, item: &mut Dk,
+    ) -> Result<(), ShahError> {
+        self.item.get(gene, item)?;
+
+        let mut pond = Pn::default();
+        self.pond.get(item.pond(), &mut pond)?;
+        if pond.origin() == new_origene {
+            return Ok(());
+        }
+
+        let mut old = *item;
+        item.gene_mut().clear();
+        self.add(new_origene, item)?;
+        self.del(gene, &mut old)
+    }
+
     pub fn list(
-        &mut self, id: GeneId, result: &mut [Dk; PAGE_SIZE],
+        &mut self, id: GeneId, result: &mut [Dk; STACK],
     ) -> Result<usize, ShahError> {
         self.item.list(id, result)
     }
+
+    /// the ducks of the origin with an order in `range`, sorted by order.
+    /// whole ponds are read until `out` can not fit the next one, so it
+    /// must have room for at least a whole stack of ducks.
+    /// `cursor` is the pond to start at, none for the head, and is set to
+    /// the pond to continue from or none when the range is done.
+    /// the ranges of the ponds do not overlap, so the order holds across
+    /// calls too
+    pub fn range(
+        &mut self, origene: &Gene, range: impl RangeBounds<u64>,
+        cursor: &mut Gene, out: &mut [Dk],
+    ) -> Result<usize, ShahError> {
+        if Dk::default().order().is_none() {
+            return derr!(self.ls, SystemError::PondNotSorted);
+        }
+        if out.len() < STACK {
+            return Err(SystemError::BadInputLength)?;
+        }
+
+        let mut origin = Og::default();
+        self.origin.get(origene, &mut origin)?;
+
+        let mut pond_gene =
+            if cursor.is_none() { *origin.head() } else { *cursor };
+        cursor.clear();
+
+        let mut pond = Pn::default();
+        let mut buf = [Dk::default(); STACK];
+        let mut count = 0usize;
+        while self.pond.get(&pond_gene, &mut pond).onf()?.is_some() {
+            if pond.origin() != origin.gene() {
+                break;
+            }
+
+            let min = pond.min().unwrap_or_default();
+            let past_end = match range.end_bound() {
+                Bound::Included(end) => min > *end,
+                Bound::Excluded(end) => min >= *end,
+                Bound::Unbounded => false,
+            };
+            if past_end {
+                break;
+            }
+
+            let max = pond.max().unwrap_or(u64::MAX);
+            let before_start = match range.start_bound() {
+                Bound::Included(start) => max < *start,
+                Bound::Excluded(start) => max <= *start,
+                Bound::Unbounded => false,
+            };
+            if !before_start {
+                if count + pond.alive() as usize > out.len() {
+                    *cursor = pond_gene;
+                    break;
+                }
+
+                self.item.list(pond.stack(), &mut buf)?;
+                for duck in buf.iter() {
+                    if count < out.len()
+                        && duck.entity_flags().is_alive()
+                        && duck.pond() == pond.gene()
+                        && duck.order().is_some_and(|o| range.contains(&o))
+                    {
+                        out[count] = *duck;
+                        count += 1;
+                    }
+                }
+            }
+
+            pond_gene = *pond.next();
+        }
+
+        out[..count].sort_by_key(|duck| duck.order());
+        Ok(count)
+    }
 }
@@ -1,21 +1,22 @@
-use super::{Duck, Origin, Pond, PondDb};
+use super::{Duck, Origin, Pond, PondCursor, PondDb};
 use crate::db::derr;
-use crate::db::entity::EntityKochFrom;
+use crate::db::entity::{EntityItem, EntityKochFrom};
 use crate::models::Gene;
-use crate::{IsNotFound, ShahError};
 use crate::{OptNotFound, SystemError};
+use crate::{PAGE_SIZE, ShahError};
 
 impl<
     Dk: Duck + EntityKochFrom<DkO, DkS>,
     Pn: Pond + EntityKochFrom<PnO, PnS>,
     Og: Origin + EntityKochFrom<OgO, OgS>,
     DkO: Duck,
-    PnO: Pond,
+    PnO: EntityItem,
     OgO: Origin,
     DkS,
     PnS,
     OgS,
-> PondDb<Dk, Pn, Og, DkO, PnO, OgO, DkS, PnS, OgS>
+    const STACK: usize,
+> PondDb<Dk, Pn, Og, DkO, PnO, OgO, DkS, PnS, OgS, STACK>
 {
     pub fn origin_get(
         &mut self, gene: &Gene, origin: &mut Og,
@@ -59,31 +60,98 @@ impl<
         *origin.tail_mut() = *old.tail();
         *origin.pond_count_mut() = old.pond_count();
         *origin.item_count_mut() = old.item_count();
+        let dying = old.entity_flags().is_dying();
+        origin.entity_flags_mut().set_is_dying(dying);
 
         self.origin.set_unchecked(origin)?;
 
         Ok(())
     }
 
-    pub fn origin_del(&mut self, gene: &Gene) -> Result<(), ShahError> {
-        let mut origin = Og::default();
-        self.origin.get(gene, &mut origin)?;
+    /// the alive ducks of every pond of the origin, a page at a time.
+    /// returns the number of ducks read, the rest of `out` is zeroed
+    pub fn origin_items(
+        &mut self, origene: &Gene, cursor: &mut PondCursor,
+        out: &mut [Dk; PAGE_SIZE],
+    ) -> Result<usize, ShahError> {
+        let mut count = 0usize;
+        if !cursor.done {
+            let mut origin = Og::default();
+            self.origin.get(origene, &mut origin)?;
+
+            if cursor.pond.is_none() {
+                cursor.slot = 0;
+                cursor.pond = if cursor.backward {
+                    *origin.tail()
+                } else {
+                    *origin.head()
+                };
+            }
+
+            let mut pond = Pn::default();
+            let mut buf = [Dk::default(); STACK];
+            while count < PAGE_SIZE {
+                if self.pond.get(&cursor.pond, &mut pond).onf()?.is_none()
+                    || pond.origin() != origin.gene()
+                {
+                    cursor.done = true;
+                    break;
+                }
+
+                if pond.stack() != 0 {
+                    self.item.list(pond.stack(), &mut buf)?;
+                }
+
+                while count < PAGE_SIZE && (cursor.slot as usize) < STACK {
+                    let slot = cursor.slot as usize;
+                    let slot =
+                        if cursor.backward { STACK - 1 - slot } else { slot };
+                    cursor.slot += 1;
+
+                    let duck = &buf[slot];
+                    if pond.stack() != 0
+                        && duck.entity_flags().is_alive()
+                        && duck.pond() == pond.gene()
+                    {
+                        out[count] = *duck;
+                        count += 1;
+                    }
+                }
 
-        let mut pond_gene = *origin.head();
-        let mut pond = Pn::default();
-        loop {
-            if let Err(e) = self.pond.get(&pond_gene, &mut pond) {
-                if e.is_not_found() {
+                if (cursor.slot as usize) < STACK {
+                    break;
+                }
+
+                cursor.slot = 0;
+                cursor.pond =
+                    if cursor.backward { *pond.past() } else { *pond.next() };
+                if cursor.pond.is_none() {
+                    cursor.done = true;
                     break;
                 }
-                return Err(e)?;
             }
-            pond_gene = *pond.next();
-            self.pond_free(&mut pond)?;
         }
 
-        self.origin.del(gene, &mut origin)?;
+        out[count..].iter_mut().for_each(|duck| duck.zeroed());
+        Ok(count)
+    }
 
-        Ok(())
+    /// mark the origin as dying and free its ponds in the background.
+    /// the sweep is persisted and resumes after a restart, the origin is
+    /// deleted once the last pond is freed
+    pub fn origin_del(&mut self, gene: &Gene) -> Result<(), ShahError> {
+        let mut origin = Og::default();
+        self.origin.get(gene, &mut origin)?;
+        if origin.entity_flags().is_dying() {
+            return Ok(());
+        }
+
+        if origin.head().is_none() {
+            return self.origin.del_unchecked(&mut origin);
+        }
+
+        origin.entity_flags_mut().set_is_dying(true);
+        self.origin.set_unchecked(&mut origin)?;
+        self.sweeper.add(origin.gene(), origin.head())
     }
 }
@@ -1,9 +1,8 @@
 use super::{Duck, Origin, Pond, PondDb};
-use crate::PAGE_SIZE;
 use crate::ShahError;
 use crate::SystemError;
 use crate::db::derr;
-use crate::db::entity::EntityKochFrom;
+use crate::db::entity::{EntityItem, EntityKochFrom};
 use crate::models::Gene;
 use std::ops::AddAssign;
 
@@ -12,15 +11,16 @@ impl<
     Pn: Pond + EntityKochFrom<PnO, PnS>,
     Og: Origin + EntityKochFrom<OgO, OgS>,
     DkO: Duck,
-    PnO: Pond,
+    PnO: EntityItem,
     OgO: Origin,
     DkS,
     PnS,
     OgS,
-> PondDb<Dk, Pn, Og, DkO, PnO, OgO, DkS, PnS, OgS>
+    const STACK: usize,
+> PondDb<Dk, Pn, Og, DkO, PnO, OgO, DkS, PnS, OgS, STACK>
 {
     pub fn pond_list(
-        &mut self, pond: &mut Pn, result: &mut [Dk; PAGE_SIZE],
+        &mut self, pond: &mut Pn, result: &mut [Dk; STACK],
     ) -> Result<(), ShahError> {
         let pond_gene = *pond.gene();
         self.pond.get(&pond_gene, pond)?;
@@ -50,27 +50,35 @@ impl<
         *pond.stack_mut() = old.stack();
         *pond.alive_mut() = old.alive();
         *pond.empty_mut() = old.empty();
+        if let (Some(min), Some(old)) = (pond.min_mut(), old.min()) {
+            *min = old;
+        }
+        if let (Some(max), Some(old)) = (pond.max_mut(), old.max()) {
+            *max = old;
+        }
 
         self.pond.set_unchecked(pond)
     }
 
     pub fn pond_free(&mut self, pond: &mut Pn) -> Result<(), ShahError> {
-        let mut buf = [Dk::default(); PAGE_SIZE];
+        let mut buf = [Dk::default(); STACK];
         self.item.list(pond.stack(), &mut buf)?;
 
+        let recycle = self.item.gene_recycle();
         *pond.empty_mut() = 0;
         for item in buf.iter_mut() {
             if item.entity_flags().is_alive() {
                 item.growth_mut().add_assign(1);
                 item.entity_flags_mut().set_is_alive(false);
-                self.item.live -= 1;
+                self.item.dead_add(item.gene());
             }
-            if !item.gene().exhausted() {
+            if !recycle.exhausted(item.gene()) {
                 *pond.empty_mut() += 1;
             }
         }
 
         self.item.write_buf_at(&buf, pond.stack())?;
+        self.item.meta_set()?;
 
         // pond.set_is_free(true);
         *pond.alive_mut() = 0;
@@ -0,0 +1,271 @@
+use super::*;
+use crate::db::entity::EntityKochFrom;
+use crate::models::task_list::Performed;
+use crate::models::{Gene, GeneId, Worker};
+use crate::{OptNotFound, PAGE_SIZE, ShahError};
+
+/// a duck that was moved to another pond by the compaction or when a
+/// full sorted pond was split.
+/// the old gene is dead, the same duck lives on at `new`
+#[derive(crate::ShahSchema)]
+#[crate::model]
+#[derive(Debug, crate::Entity)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, utoipa::ToSchema))]
+pub struct PondRemap {
+    pub gene: Gene,
+    pub old: Gene,
+    pub new: Gene,
+    pub origin: Gene,
+    /// the order of the moves, starting at 1
+    pub seq: u64,
+    growth: u64,
+    entity_flags: EntityFlags,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    _pad: [u8; 7],
+}
+
+impl<
+    Dk: Duck + EntityKochFrom<DkO, DkS>,
+    Pn: Pond + EntityKochFrom<PnO, PnS>,
+    Og: Origin + EntityKochFrom<OgO, OgS>,
+    DkO: Duck,
+    PnO: EntityItem,
+    OgO: Origin,
+    DkS,
+    PnS,
+    OgS,
+    const STACK: usize,
+> PondDb<Dk, Pn, Og, DkO, PnO, OgO, DkS, PnS, OgS, STACK>
+{
+    /// look for sparse ponds, a page of ponds at a time
+    pub(super) fn work_compact(&mut self) -> Result<Performed, ShahError> {
+        if self.remap.work()?.0 {
+            return Ok(Performed(true));
+        }
+        if self.compact == 0 {
+            return Ok(Performed(false));
+        }
+
+        let mut page = [Pn::default(); PAGE_SIZE];
+        let id = self.compact_cursor;
+        let Some(len) = self.pond.list(id, &mut page).onf()? else {
+            self.compact_cursor = GeneId(1);
+            return Ok(Performed(false));
+        };
+        self.compact_cursor =
+            if len < PAGE_SIZE { GeneId(1) } else { id + len as u64 };
+
+        for pond in page[..len].iter() {
+            if pond.gene().id == 0
+                || !pond.entity_flags().is_alive()
+                || pond.origin().is_none()
+                || pond.alive() == 0
+                || pond.alive() > self.compact
+            {
+                continue;
+            }
+
+            if self.pond_compact(pond.gene())? {
+                self.compact_cursor = pond.gene().id + 1;
+                return Ok(Performed(true));
+            }
+        }
+
+        Ok(Performed(false))
+    }
+
+    /// move every duck of the pond to its past or next pond and free it.
+    /// the neighbour must have free slots for all of them and at least as
+    /// many alive ducks. returns false when there is no such neighbour
+    fn pond_compact(&mut self, gene: &Gene) -> Result<bool, ShahError> {
+        let mut pond = Pn::default();
+        let mut origin = Og::default();
+        if self.pond.get(gene, &mut pond).onf()?.is_none()
+            || self.origin.get(pond.origin(), &mut origin).onf()?.is_none()
+            || origin.entity_flags().is_dying()
+        {
+            return Ok(false);
+        }
+
+        let mut buf = [Dk::default(); STACK];
+        let recycle = self.item.gene_recycle();
+        let mut target = Pn::default();
+        let mut found = false;
+        for near in [*pond.past(), *pond.next()] {
+            if self.pond.get(&near, &mut target).onf()?.is_none()
+                || target.origin() != origin.gene()
+                || target.alive() < pond.alive()
+            {
+                continue;
+            }
+
+            // deleted slots are free too, not only the empty ones
+            self.item.list(target.stack(), &mut buf)?;
+            let room = buf
+                .iter()
+                .filter(|d| {
+                    !d.entity_flags().is_alive() && !recycle.exhausted(d.gene())
+                })
+                .count();
+            if room >= pond.alive() as usize {
+                found = true;
+                break;
+            }
+        }
+        if !found {
+            return Ok(false);
+        }
+
+        self.item.list(pond.stack(), &mut buf)?;
+        for duck in buf.iter_mut() {
+            if !duck.entity_flags().is_alive() || duck.pond() != pond.gene() {
+                continue;
+            }
+
+            self.duck_move(duck, &mut target, origin.gene())?;
+        }
+        self.remap_prune()?;
+
+        self.pond.set(&mut target)?;
+        log::info!(
+            "{} compacted {:?} into {:?}",
+            self.ls,
+            pond.gene(),
+            target.gene()
+        );
+
+        *pond.alive_mut() = 0;
+        self.add_empty_pond(&mut origin, pond)?;
+        self.origin.set(&mut origin)?;
+
+        Ok(true)
+    }
+
+    /// move an alive duck into a free slot of the target and log the
+    /// move. the target is not written
+    pub(super) fn duck_move(
+        &mut self, duck: &mut Dk, target: &mut Pn, origin: &Gene,
+    ) -> Result<(), ShahError> {
+        let mut moved = *duck;
+        moved.gene_mut().clear();
+        if let Some(order) = duck.order() {
+            Self::pond_order(target, order);
+        }
+        *target.alive_mut() += 1;
+        self.pond_put(target, &mut moved)?;
+        self.item.del_unchecked(duck)?;
+
+        self.remap_seq += 1;
+        let mut remap = PondRemap {
+            old: *duck.gene(),
+            new: *moved.gene(),
+            origin: *origin,
+            seq: self.remap_seq,
+            ..Default::default()
+        };
+        self.remap.add(&mut remap)?;
+        Ok(())
+    }
+
+    /// one record of the remap log, none past its end
+    fn remap_at(&mut self, id: u64) -> Result<Option<PondRemap>, ShahError> {
+        let mut one = [PondRemap::default()];
+        Ok(self.remap.list(GeneId(id), &mut one).onf()?.map(|_| one[0]))
+    }
+
+    /// the first id in `lo..hi` where `pred` holds. `pred` must hold
+    /// for every id after that, which it does for the seq and for the
+    /// alive flag since remaps are added at the end and deleted from
+    /// the front
+    fn remap_search(
+        &mut self, mut lo: u64, mut hi: u64, pred: impl Fn(&PondRemap) -> bool,
+    ) -> Result<u64, ShahError> {
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.remap_at(mid)? {
+                Some(remap) if !pred(&remap) => lo = mid + 1,
+                _ => hi = mid,
+            }
+        }
+        Ok(lo)
+    }
+
+    /// find the last seq and the first kept remap without reading the
+    /// whole log
+    pub(super) fn remap_init(&mut self) -> Result<(), ShahError> {
+        let end = self.remap.count()?.total.0 + 1;
+        self.remap_front = GeneId(self.remap_search(1, end, |r| {
+            r.gene.id != 0 && r.entity_flags.is_alive()
+        })?);
+        if end > 1
+            && let Some(last) = self.remap_at(end - 1)?
+        {
+            self.remap_seq = last.seq;
+        }
+        Ok(())
+    }
+
+    /// delete the remaps that are older than the last `remap_keep`,
+    /// from the front of the log
+    pub(super) fn remap_prune(&mut self) -> Result<(), ShahError> {
+        if self.remap_seq <= self.remap_keep {
+            return Ok(());
+        }
+        let oldest = self.remap_seq - self.remap_keep;
+        let mut page = [PondRemap::default(); PAGE_SIZE];
+        loop {
+            let id = self.remap_front;
+            let Some(count) = self.remap.list(id, &mut page).onf()? else {
+                return Ok(());
+            };
+            for remap in page[..count].iter_mut() {
+                if remap.seq > oldest {
+                    return Ok(());
+                }
+                if remap.gene.id != 0 && remap.entity_flags.is_alive() {
+                    self.remap.del_unchecked(remap)?;
+                }
+                self.remap_front += 1;
+            }
+            if count < PAGE_SIZE {
+                return Ok(());
+            }
+        }
+    }
+
+    /// the ducks moved to another pond with a seq after `after`, oldest
+    /// first. keep the seq of the last one read and pass it next time.
+    /// only the last moves are kept, see [`Self::set_remap_keep`], so if
+    /// the first seq is not `after + 1` some moves were missed
+    pub fn remap_list(
+        &mut self, after: u64, result: &mut [PondRemap],
+    ) -> Result<usize, ShahError> {
+        let end = self.remap.count()?.total.0 + 1;
+        let front = self.remap_front.0.max(1);
+        let mut id = self.remap_search(front, end, |r| r.seq > after)?;
+
+        let mut count = 0;
+        let mut page = [PondRemap::default(); PAGE_SIZE];
+        while count < result.len() {
+            let Some(len) = self.remap.list(GeneId(id), &mut page).onf()?
+            else {
+                break;
+            };
+            for remap in page[..len].iter() {
+                if count < result.len()
+                    && remap.gene.id != 0
+                    && remap.entity_flags.is_alive()
+                    && remap.seq > after
+                {
+                    result[count] = *remap;
+                    count += 1;
+                }
+            }
+            if len < PAGE_SIZE {
+                break;
+            }
+            id += PAGE_SIZE as u64;
+        }
+        Ok(count)
+    }
+}
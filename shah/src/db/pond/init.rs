@@ -1,23 +1,27 @@
 use super::*;
-use crate::ShahError;
 use crate::config::ShahConfig;
-use crate::db::entity::{EntityDb, EntityKochFrom};
+use crate::db::entity::{ENTITY_META, EntityDb, EntityKochFrom};
+use crate::db::sweep::Sweeper;
 use crate::models::Worker;
 use crate::models::task_list::{Performed, Task, TaskList};
-use crate::models::{DeadList, Gene};
-use crate::{BLOCK_SIZE, utils};
+use crate::models::{Binary, DeadList, Gene, GeneId};
+use crate::{BLOCK_SIZE, PAGE_SIZE, utils};
+use crate::{DbError, OptNotFound, ShahError, SystemError};
+use std::os::unix::fs::FileExt;
+use std::path::Path;
 
 impl<
     Dk: Duck + EntityKochFrom<DkO, DkS>,
     Pn: Pond + EntityKochFrom<PnO, PnS>,
     Og: Origin + EntityKochFrom<OgO, OgS>,
     DkO: Duck,
-    PnO: Pond,
+    PnO: EntityItem,
     OgO: Origin,
     DkS,
     PnS,
     OgS,
-> PondDb<Dk, Pn, Og, DkO, PnO, OgO, DkS, PnS, OgS>
+    const STACK: usize,
+> PondDb<Dk, Pn, Og, DkO, PnO, OgO, DkS, PnS, OgS, STACK>
 {
     pub fn new(
         path: &str, revision: u16, pond_revision: u16, origin_revision: u16,
@@ -31,26 +35,77 @@ impl<
 
         utils::validate_db_name(name)?;
 
+        if Dk::default().order().is_some() && Pn::default().min().is_none() {
+            log::error!(
+                "<PondDb {path} /> ducks with order need a sorted pond"
+            );
+            return Err(SystemError::PondNotSorted)?;
+        }
+
         std::fs::create_dir_all(&data_path)?;
 
         let mut db = Self {
             free_list: DeadList::<Gene, BLOCK_SIZE>::new(),
+            free_scan: GeneId(1),
             item: EntityDb::<Dk, DkO, DkS>::new(path, revision)?,
             pond: EntityDb::new(&format!("{path}/index"), pond_revision)?,
             origin: EntityDb::new(&format!("{path}/origin"), origin_revision)?,
+            sweeper: Sweeper::new(path)?,
+            remap: EntityDb::new(&format!("{path}/remap"), 1)?,
+            remap_seq: 0,
+            remap_front: GeneId(1),
+            remap_keep: BLOCK_SIZE as u64,
+            compact: 0,
+            compact_cursor: GeneId(1),
             tasks: TaskList::new([
                 Self::work_item,
                 Self::work_pond,
                 Self::work_origin,
+                Self::work_quarantine,
+                Self::work_sweep,
+                Self::work_compact,
+                Self::work_free_scan,
             ]),
             ls: format!("<PondDb {path}.{revision} />"),
         };
 
         db.item.set_dead_list_disabled(true);
+        // remaps are only added at the end, in the order of their seq
+        db.remap.set_dead_list_disabled(true);
+        db.init_meta(&data_path.join("meta.shah"), name)?;
+        db.remap_init()?;
+        db.sweep_resume()?;
 
         Ok(db)
     }
 
+    /// the stack size can not change once there are ducks. dbs from
+    /// before the meta file always used [`PAGE_SIZE`]
+    fn init_meta(&mut self, path: &Path, name: &str) -> Result<(), ShahError> {
+        if STACK == 0 || STACK > u16::MAX as usize {
+            log::error!("{} invalid stack size: {STACK}", self.ls);
+            return Err(DbError::InvalidDbMeta)?;
+        }
+
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let mut meta = PondMeta::default();
+        if file.metadata()?.len() < PondMeta::N {
+            let used = self.item.file_size()? > ENTITY_META + Dk::N;
+            meta.init(name, if used { PAGE_SIZE } else { STACK });
+            file.write_all_at(meta.as_binary(), 0)?;
+        } else {
+            file.read_exact_at(meta.as_binary_mut(), 0)?;
+        }
+
+        meta.check(&self.ls, STACK)
+    }
+
     fn work_item(&mut self) -> Result<Performed, ShahError> {
         self.item.work()
     }
@@ -62,6 +117,146 @@ impl<
     fn work_origin(&mut self) -> Result<Performed, ShahError> {
         self.origin.work()
     }
+
+    /// give the slots that finished their quarantine back to their pond
+    fn work_quarantine(&mut self) -> Result<Performed, ShahError> {
+        let mut slot = [Dk::default()];
+        let mut pond = Pn::default();
+        let mut performed = false;
+        for _ in 0..10 {
+            let Some(id) = self.item.quarantine_pop() else { break };
+            performed = true;
+
+            if self.item.list(id, &mut slot).onf()?.is_none() {
+                continue;
+            }
+            let item = &mut slot[0];
+            if item.entity_flags().is_alive() || !item.gene().exhausted() {
+                continue;
+            }
+
+            item.gene_mut().iter = 0;
+            self.item.set_unchecked(item)?;
+
+            if self.pond.get(item.pond(), &mut pond).onf()?.is_some() {
+                *pond.empty_mut() = pond.empty().saturating_add(1);
+                self.pond.set_unchecked(&mut pond)?;
+            }
+        }
+
+        Ok(Performed(performed))
+    }
+
+    /// fill the free list with the ponds freed before the last restart
+    fn work_free_scan(&mut self) -> Result<Performed, ShahError> {
+        if self.free_scan == 0 {
+            return Ok(Performed(false));
+        }
+
+        let mut page = [Pn::default(); PAGE_SIZE];
+        let id = self.free_scan;
+        let Some(len) = self.pond.list(id, &mut page).onf()? else {
+            self.free_scan = GeneId(0);
+            return Ok(Performed(false));
+        };
+
+        for pond in page[..len].iter() {
+            if pond.gene().id != 0
+                && pond.entity_flags().is_alive()
+                && pond.alive() == 0
+            {
+                self.free_list.push(*pond.gene());
+            }
+        }
+
+        self.free_scan = id + len as u64;
+        if len < PAGE_SIZE || self.free_list.is_full() {
+            log::info!("{} free ponds: {}", self.ls, self.free_list.len());
+            self.free_scan = GeneId(0);
+        }
+
+        Ok(Performed(true))
+    }
+
+    /// free the ponds of the dying origins, a few at a time.
+    /// the progress is saved after every pond, so after a crash at most
+    /// one freed pond is found again. it has nothing alive and is skipped
+    fn work_sweep(&mut self) -> Result<Performed, ShahError> {
+        if self.sweeper.work()?.0 {
+            return Ok(Performed(true));
+        }
+
+        let Some(mut sweep) = self.sweeper.current()? else {
+            return Ok(Performed(false));
+        };
+
+        let mut pond = Pn::default();
+        for _ in 0..4 {
+            if self.pond.get(&sweep.next, &mut pond).onf()?.is_none()
+                || pond.origin() != &sweep.parent
+            {
+                sweep.next.clear();
+            } else {
+                if pond.alive() > 0 {
+                    self.pond_free(&mut pond)?;
+                    sweep.done += 1;
+                }
+                sweep.next = *pond.next();
+            }
+
+            let done = sweep.next.is_none();
+            if done {
+                self.origin_swept(&sweep.parent)?;
+            }
+            self.sweeper.update(&mut sweep)?;
+            if done {
+                break;
+            }
+        }
+
+        Ok(Performed(true))
+    }
+
+    /// move the sweeps past the ponds that were freed right before a
+    /// crash, before they can be taken from the free list by another
+    /// origin and cut the sweep short
+    fn sweep_resume(&mut self) -> Result<(), ShahError> {
+        let mut pond = Pn::default();
+        for mut sweep in self.sweeper.sweeps()? {
+            let next = sweep.next;
+            while self.pond.get(&sweep.next, &mut pond).onf()?.is_some()
+                && pond.origin() == &sweep.parent
+                && pond.alive() == 0
+            {
+                sweep.next = *pond.next();
+            }
+            if sweep.next == next {
+                continue;
+            }
+
+            if sweep.next.is_none() {
+                self.origin_swept(&sweep.parent)?;
+            }
+            self.sweeper.update(&mut sweep)?;
+        }
+        Ok(())
+    }
+
+    /// delete the dying origin once all of its ponds are freed
+    fn origin_swept(&mut self, gene: &Gene) -> Result<(), ShahError> {
+        let mut origin = Og::default();
+        if self.origin.get(gene, &mut origin).onf()?.is_some()
+            && origin.entity_flags().is_dying()
+        {
+            self.origin.del_unchecked(&mut origin)?;
+        }
+        Ok(())
+    }
+
+    /// number of deleted origins whose ponds are still being freed
+    pub fn sweep_pending(&self) -> usize {
+        self.sweeper.pending()
+    }
 }
 
 impl<
@@ -69,14 +264,15 @@ impl<
     Pn: Pond + EntityKochFrom<PnO, PnS>,
     Og: Origin + EntityKochFrom<OgO, OgS>,
     DkO: Duck,
-    PnO: Pond,
+    PnO: EntityItem,
     OgO: Origin,
     DkS,
     PnS,
     OgS,
-> Worker<3> for PondDb<Dk, Pn, Og, DkO, PnO, OgO, DkS, PnS, OgS>
+    const STACK: usize,
+> Worker<7> for PondDb<Dk, Pn, Og, DkO, PnO, OgO, DkS, PnS, OgS, STACK>
 {
-    fn tasks(&mut self) -> &mut TaskList<3, Task<Self>> {
+    fn tasks(&mut self) -> &mut TaskList<7, Task<Self>> {
         &mut self.tasks
     }
 }
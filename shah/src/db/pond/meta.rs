@@ -0,0 +1,33 @@
+use crate::models::{DbHead, ShahMagic, ShahMagicDb};
+use crate::{DbError, ShahError};
+
+pub const POND_VERSION: u16 = 1;
+pub const POND_MAGIC: ShahMagic =
+    ShahMagic::new_const(ShahMagicDb::Pond as u16);
+
+/// the layout of a pond db, kept in `meta.shah` next to the items
+#[crate::model]
+#[derive(Debug)]
+pub struct PondMeta {
+    pub db: DbHead,
+    /// number of ducks in a pond
+    pub stack: u64,
+}
+
+impl PondMeta {
+    pub fn init(&mut self, name: &str, stack: usize) {
+        self.db.init(POND_MAGIC, 0, name, POND_VERSION);
+        self.stack = stack as u64;
+    }
+
+    pub fn check(&self, ls: &str, stack: usize) -> Result<(), ShahError> {
+        self.db.check(ls, POND_MAGIC, 0, POND_VERSION)?;
+
+        if self.stack != stack as u64 {
+            log::error!("{ls} stack size changed. {} != {stack}", self.stack);
+            return Err(DbError::InvalidDbMeta)?;
+        }
+
+        Ok(())
+    }
+}
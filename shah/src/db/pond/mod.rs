@@ -1,23 +1,25 @@
 use super::entity::{EntityDb, EntityItem, EntityKochFrom};
-use crate::BLOCK_SIZE;
 use crate::db::entity::EntityFlags;
+use crate::db::sweep::Sweeper;
 use crate::models::task_list::{Task, TaskList};
 use crate::models::{DeadList, Gene, GeneId};
+use crate::{BLOCK_SIZE, PAGE_SIZE};
 
 use std::fmt::Debug;
 
 mod api_item;
 mod api_origin;
 mod api_pond;
+mod compact;
 
 mod init;
+mod meta;
+pub mod old;
 mod options;
 mod util;
 
-// NOTE's for sorted ponds.
-// 1. we dont need to sort items in each "stack" and we can just leave that
-//    for frontend. just have a min/max value in each "pond" and
-//    if item > pond.max then move it to pond.past ...
+pub use compact::PondRemap;
+pub use meta::*;
 
 pub trait Origin: EntityItem {
     fn head(&self) -> &Gene;
@@ -59,10 +61,26 @@ pub trait Pond: EntityItem {
     fn stack(&self) -> GeneId;
     fn stack_mut(&mut self) -> &mut GeneId;
 
-    fn alive(&self) -> u8;
-    fn alive_mut(&mut self) -> &mut u8;
-    fn empty(&self) -> u8;
-    fn empty_mut(&mut self) -> &mut u8;
+    fn alive(&self) -> u16;
+    fn alive_mut(&mut self) -> &mut u16;
+    fn empty(&self) -> u16;
+    fn empty_mut(&mut self) -> &mut u16;
+
+    /// the smallest order of the ducks that were added to this pond.
+    /// ponds of an origin are kept sorted by it
+    fn min(&self) -> Option<u64> {
+        None
+    }
+    fn min_mut(&mut self) -> Option<&mut u64> {
+        None
+    }
+    /// the largest order of the ducks that were added to this pond
+    fn max(&self) -> Option<u64> {
+        None
+    }
+    fn max_mut(&mut self) -> Option<&mut u64> {
+        None
+    }
 }
 
 #[derive(crate::ShahSchema)]
@@ -76,21 +94,77 @@ pub struct ShahPond {
     pub origin: Gene,
     pub stack: GeneId,
     pub growth: u64,
+    pub alive: u16,
+    /// not iter exhausted slots.
+    /// in other words slots that did not used all of their gene.iter
+    pub empty: u16,
     pub entity_flags: EntityFlags,
     // NOTE: is_free flags is set but never read
     // #[flags(is_free)]
     // pub flags: u8,
-    pub alive: u8,
-    /// not iter exhausted slots.
-    /// in other words slots that did not used all of their gene.iter
-    pub empty: u8,
     #[cfg_attr(feature = "serde", serde(skip))]
-    _pad: [u8; 5],
+    _pad: [u8; 3],
+}
+
+/// where [`PondDb::origin_items`] continues.
+/// a none pond starts at the head, or the tail when `backward`
+#[crate::model]
+#[derive(Debug, PartialEq, Eq)]
+pub struct PondCursor {
+    pub pond: Gene,
+    /// number of slots of `pond` that are already read
+    pub slot: u16,
+    pub backward: bool,
+    /// the last pond was read
+    pub done: bool,
+    _pad: [u8; 4],
+}
+
+impl PondCursor {
+    pub const HEAD: Self = Self {
+        pond: Gene::NONE,
+        slot: 0,
+        backward: false,
+        done: false,
+        _pad: [0; 4],
+    };
+    pub const TAIL: Self = Self { backward: true, ..Self::HEAD };
+}
+
+/// a pond for ducks with an order.
+/// the ducks of a pond are not sorted, only the ponds of an origin are
+#[derive(crate::ShahSchema)]
+#[crate::model]
+#[derive(Debug, crate::Entity, crate::Pond)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, utoipa::ToSchema))]
+pub struct ShahSortedPond {
+    pub gene: Gene,
+    pub next: Gene,
+    pub past: Gene,
+    pub origin: Gene,
+    pub stack: GeneId,
+    pub growth: u64,
+    #[pond(min)]
+    pub min: u64,
+    #[pond(max)]
+    pub max: u64,
+    pub alive: u16,
+    pub empty: u16,
+    pub entity_flags: EntityFlags,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    _pad: [u8; 3],
 }
 
 pub trait Duck: EntityItem {
     fn pond(&self) -> &Gene;
     fn pond_mut(&mut self) -> &mut Gene;
+    /// the sort key of this duck. it is fixed once the duck is added
+    fn order(&self) -> Option<u64> {
+        None
+    }
+    fn order_mut(&mut self) -> Option<&mut u64> {
+        None
+    }
 }
 
 #[derive(Debug)]
@@ -100,17 +174,32 @@ pub struct PondDb<
     Og: Origin + EntityKochFrom<OgO, OgS> = ShahOrigin,
     // old
     DkO: Duck = Dk,
-    PnO: Pond = Pn,
+    PnO: EntityItem = Pn,
     OgO: Origin = Og,
     // state
     DkS = (),
     PnS = (),
     OgS = (),
+    // ducks per pond, fixed once the db has data
+    const STACK: usize = PAGE_SIZE,
 > {
     item: EntityDb<Dk, DkO, DkS>,
     pond: EntityDb<Pn, PnO, PnS>,
     origin: EntityDb<Og, OgO, OgS>,
     free_list: DeadList<Gene, BLOCK_SIZE>,
+    /// the next pond to check for the free list, 0 once all are checked
+    free_scan: GeneId,
+    sweeper: Sweeper,
+    remap: EntityDb<PondRemap>,
+    /// the seq of the last remap
+    remap_seq: u64,
+    /// the first remap that is not deleted yet
+    remap_front: GeneId,
+    /// number of remaps to keep
+    remap_keep: u64,
+    /// ponds with this many alive ducks or less are compacted. 0 is off
+    compact: u16,
+    compact_cursor: GeneId,
     ls: String,
-    tasks: TaskList<3, Task<Self>>,
+    tasks: TaskList<7, Task<Self>>,
 }
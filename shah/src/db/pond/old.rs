@@ -0,0 +1,45 @@
+//! the pond layouts from before `alive` and `empty` were `u16`.
+//! open the old index with a new pond revision and koch from these:
+//! `PondDb<Dk, ShahPond, ShahOrigin, Dk, old::ShahPond>` and
+//! [`PondDb::set_pond_koch`] with the old revision of `{path}/index`
+
+use std::cell::RefMut;
+
+use crate::ShahError;
+use crate::db::entity::{EntityFlags, EntityKochFrom};
+use crate::models::{Gene, GeneId};
+
+#[derive(crate::ShahSchema)]
+#[crate::model]
+#[derive(Debug, crate::Entity)]
+pub struct ShahPond {
+    pub gene: Gene,
+    pub next: Gene,
+    pub past: Gene,
+    pub origin: Gene,
+    pub stack: GeneId,
+    pub growth: u64,
+    pub entity_flags: EntityFlags,
+    pub alive: u8,
+    pub empty: u8,
+    _pad: [u8; 5],
+}
+
+impl<S> EntityKochFrom<ShahPond, S> for super::ShahPond {
+    fn entity_koch_from(
+        old: ShahPond, _: RefMut<S>,
+    ) -> Result<Self, ShahError> {
+        Ok(Self {
+            gene: old.gene,
+            next: old.next,
+            past: old.past,
+            origin: old.origin,
+            stack: old.stack,
+            growth: old.growth,
+            alive: old.alive as u16,
+            empty: old.empty as u16,
+            entity_flags: old.entity_flags,
+            ..Default::default()
+        })
+    }
+}
@@ -2,18 +2,20 @@ use super::*;
 use crate::ShahError;
 use crate::db::entity::EntityKoch;
 use crate::db::entity::EntityKochFrom;
+use crate::models::GeneRecycle;
 
 impl<
     Dk: Duck + EntityKochFrom<DkO, DkS>,
     Pn: Pond + EntityKochFrom<PnO, PnS>,
     Og: Origin + EntityKochFrom<OgO, OgS>,
     DkO: Duck,
-    PnO: Pond,
+    PnO: EntityItem,
     OgO: Origin,
     DkS,
     PnS,
     OgS,
-> PondDb<Dk, Pn, Og, DkO, PnO, OgO, DkS, PnS, OgS>
+    const STACK: usize,
+> PondDb<Dk, Pn, Og, DkO, PnO, OgO, DkS, PnS, OgS, STACK>
 {
     pub fn set_koch(
         &mut self, koch: Option<EntityKoch<Dk, DkO, DkS>>,
@@ -33,6 +35,24 @@ impl<
         self.origin.set_koch(koch)
     }
 
+    /// recycle policy for the item slots
+    pub fn set_gene_recycle(&mut self, recycle: GeneRecycle) {
+        self.item.set_gene_recycle(recycle);
+    }
+
+    /// compact ponds with `threshold` alive ducks or less into their
+    /// neighbours. moved ducks get new genes, see [`Self::remap_list`].
+    /// 0 turns it off, which is the default
+    pub fn set_compact(&mut self, threshold: u16) {
+        self.compact = threshold.min(STACK as u16);
+    }
+
+    /// how many of the last remaps are kept for [`Self::remap_list`].
+    /// the older ones are deleted as new ducks are moved
+    pub fn set_remap_keep(&mut self, keep: u64) {
+        self.remap_keep = keep.max(1);
+    }
+
     pub fn set_work_iter(&mut self, work_iter: usize) {
         self.item.set_work_iter(work_iter);
     }
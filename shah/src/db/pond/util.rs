@@ -1,23 +1,33 @@
 use super::*;
-use crate::ShahError;
+use crate::OptNotFound;
+use crate::config::ShahConfig;
 use crate::db::entity::{ENTITY_META, EntityKochFrom};
 use crate::models::{Binary, Gene, GeneId};
-use crate::{OptNotFound, PAGE_SIZE};
+use crate::{ShahError, SystemError};
 
 impl<
     Dk: Duck + EntityKochFrom<DkO, DkS>,
     Pn: Pond + EntityKochFrom<PnO, PnS>,
     Og: Origin + EntityKochFrom<OgO, OgS>,
     DkO: Duck,
-    PnO: Pond,
+    PnO: EntityItem,
     OgO: Origin,
     DkS,
     PnS,
     OgS,
-> PondDb<Dk, Pn, Og, DkO, PnO, OgO, DkS, PnS, OgS>
+    const STACK: usize,
+> PondDb<Dk, Pn, Og, DkO, PnO, OgO, DkS, PnS, OgS, STACK>
 {
-    fn take_free(&mut self) -> Option<Gene> {
-        self.free_list.pop(|_| true)
+    /// a pond from the free list that is still free. the startup scan
+    /// may add a pond that was already taken, so it is checked again
+    fn take_free(&mut self, pond: &mut Pn) -> Result<bool, ShahError> {
+        while let Some(free) = self.free_list.pop(|_| true) {
+            if self.pond.get(&free, pond).onf()?.is_some() && pond.alive() == 0
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
     }
 
     pub(super) fn add_empty_pond(
@@ -28,13 +38,14 @@ impl<
         //     origin.pond_ -= 1;
         // }
 
-        let mut buf = [Dk::default(); PAGE_SIZE];
+        let mut buf = [Dk::default(); STACK];
         self.item.list(pond.stack(), &mut buf)?;
 
+        let recycle = self.item.gene_recycle();
         *pond.empty_mut() = 0;
         *pond.alive_mut() = 0;
         for item in buf {
-            if !item.gene().exhausted() {
+            if !recycle.exhausted(item.gene()) {
                 *pond.empty_mut() += 1;
             }
             if item.entity_flags().is_alive() {
@@ -76,10 +87,57 @@ impl<
         Ok(())
     }
 
+    /// a free or new pond for the origin. it is not linked yet
+    fn new_pond(&mut self, origin: &mut Og) -> Result<Pn, ShahError> {
+        let mut new = Pn::default();
+        if !self.take_free(&mut new)? {
+            new.gene_mut().clear();
+            self.pond.add(&mut new)?;
+        }
+        *new.alive_mut() = 0;
+        *new.origin_mut() = *origin.gene();
+        // new.set_is_free(false);
+
+        *origin.pond_count_mut() += 1;
+
+        Ok(new)
+    }
+
+    /// link the pond after `past` or at the head when `past` is none.
+    /// the neighbours are written, the new pond is not
+    fn link_pond(
+        &mut self, origin: &mut Og, new: &mut Pn, past: &Gene,
+    ) -> Result<(), ShahError> {
+        let mut temp = Pn::default();
+
+        let next = if self.pond.get(past, &mut temp).onf()?.is_some() {
+            let next = *temp.next();
+            *temp.next_mut() = *new.gene();
+            *new.past_mut() = *past;
+            self.pond.set(&mut temp)?;
+            next
+        } else {
+            new.past_mut().clear();
+            let head = *origin.head();
+            *origin.head_mut() = *new.gene();
+            head
+        };
+
+        if self.pond.get(&next, &mut temp).onf()?.is_some() {
+            *temp.past_mut() = *new.gene();
+            *new.next_mut() = next;
+            self.pond.set(&mut temp)?;
+        } else {
+            new.next_mut().clear();
+            *origin.tail_mut() = *new.gene();
+        }
+
+        Ok(())
+    }
+
     pub(super) fn half_empty_pond(
         &mut self, origin: &mut Og,
     ) -> Result<Pn, ShahError> {
-        let mut last = Pn::default();
         let mut pond_gene = *origin.tail();
         let mut temp = Pn::default();
         loop {
@@ -87,46 +145,194 @@ impl<
                 break;
             }
 
-            if temp.gene() == origin.tail() {
-                last.clone_from(&temp);
-            }
-
             if temp.empty() > 0 {
                 return Ok(temp);
             }
             pond_gene = *temp.past();
         }
 
-        let mut new = Pn::default();
-        let add_new = if let Some(free) = self.take_free() {
-            self.pond.get(&free, &mut new).onf()?.is_none()
-        } else {
-            true
-        };
+        let mut new = self.new_pond(origin)?;
+        let tail = *origin.tail();
+        self.link_pond(origin, &mut new, &tail)?;
+        Ok(new)
+    }
 
-        if add_new {
-            new.gene_mut().clear();
-            self.pond.add(&mut new)?;
+    /// the pond for a duck with this order. it goes to the last pond
+    /// with a min not greater than the order. when that pond is full a
+    /// new pond is started right after it, or the pond is split in two
+    /// when the order falls inside it. so the ranges of the ponds never
+    /// overlap. a split moves ducks, see [`PondRemap`]
+    pub(super) fn sorted_pond(
+        &mut self, origin: &mut Og, order: u64,
+    ) -> Result<Pn, ShahError> {
+        let mut past = Gene::NONE;
+        let mut pond_gene = *origin.tail();
+        let mut temp = Pn::default();
+        while self.pond.get(&pond_gene, &mut temp).onf()?.is_some() {
+            if temp.min().unwrap_or_default() <= order {
+                if temp.empty() > 0 {
+                    return Ok(temp);
+                }
+                if order < temp.max().unwrap_or_default() {
+                    return self.pond_split(origin, temp, order);
+                }
+                past = pond_gene;
+                break;
+            }
+
+            pond_gene = *temp.past();
+            // nothing comes before the head, so it can take smaller orders
+            if pond_gene.is_none() && temp.empty() > 0 {
+                return Ok(temp);
+            }
         }
-        new.next_mut().clear();
-        *new.alive_mut() = 0;
-        *new.origin_mut() = *origin.gene();
-        // new.set_is_free(false);
 
-        *origin.pond_count_mut() += 1;
+        let mut new = self.new_pond(origin)?;
+        self.link_pond(origin, &mut new, &past)?;
+        Ok(new)
+    }
 
-        if last.entity_flags().is_alive() {
-            *last.next_mut() = *new.gene();
-            *new.past_mut() = *origin.tail();
-            *origin.tail_mut() = *new.gene();
-            self.pond.set(&mut last)?;
+    /// move the upper half of a full pond to a new pond right after it.
+    /// returns the one of the two that the order belongs in, the other
+    /// one is written
+    fn pond_split(
+        &mut self, origin: &mut Og, mut pond: Pn, order: u64,
+    ) -> Result<Pn, ShahError> {
+        let gene = *pond.gene();
+        let mut new = self.new_pond(origin)?;
+        self.link_pond(origin, &mut new, &gene)?;
+        // linking wrote the next of the pond
+        self.pond.get(&gene, &mut pond)?;
+
+        let mut buf = [Dk::default(); STACK];
+        self.item.list(pond.stack(), &mut buf)?;
+        let mut ducks = (0..STACK)
+            .filter(|x| {
+                buf[*x].entity_flags().is_alive() && buf[*x].pond() == &gene
+            })
+            .collect::<Vec<_>>();
+        ducks.sort_by_key(|x| buf[*x].order());
+        for x in ducks.split_off(ducks.len().div_ceil(2)) {
+            self.duck_move(&mut buf[x], &mut new, origin.gene())?;
+        }
+        self.remap_prune()?;
+
+        // count what is left, the moved slots are free again
+        let recycle = self.item.gene_recycle();
+        self.item.list(pond.stack(), &mut buf)?;
+        *pond.alive_mut() = 0;
+        *pond.empty_mut() = 0;
+        for duck in buf.iter() {
+            if duck.entity_flags().is_alive() && duck.pond() == &gene {
+                if let Some(order) = duck.order() {
+                    Self::pond_order(&mut pond, order);
+                }
+                *pond.alive_mut() += 1;
+            } else if !recycle.exhausted(duck.gene()) {
+                *pond.empty_mut() += 1;
+            }
+        }
+
+        log::info!("{} split {gene:?} into {:?}", self.ls, new.gene());
+        let upper = new.alive() > 0 && new.min().is_some_and(|m| order >= m);
+        if upper || pond.empty() == 0 {
+            self.pond.set(&mut pond)?;
+            Ok(new)
         } else {
-            new.past_mut().clear();
-            *origin.head_mut() = *new.gene();
-            *origin.tail_mut() = *new.gene();
+            self.pond.set(&mut new)?;
+            Ok(pond)
         }
+    }
 
-        Ok(new)
+    /// widen the min and max of the pond to include the order
+    pub(super) fn pond_order(pond: &mut Pn, order: u64) {
+        let fresh = pond.alive() == 0;
+        if let Some(min) = pond.min_mut()
+            && (fresh || order < *min)
+        {
+            *min = order;
+        }
+        if let Some(max) = pond.max_mut()
+            && (fresh || order > *max)
+        {
+            *max = order;
+        }
+    }
+
+    /// put a new duck in a free slot of the pond and give it a gene.
+    /// the pond is not written
+    pub(super) fn pond_put(
+        &mut self, pond: &mut Pn, item: &mut Dk,
+    ) -> Result<(), ShahError> {
+        let mut buf = [Dk::default(); STACK];
+        *item.pond_mut() = *pond.gene();
+        *item.growth_mut() = 0;
+        let recycle = self.item.gene_recycle();
+        let ig = item.gene_mut();
+        ig.server = ShahConfig::get().server;
+        crate::utils::getrandom(&mut ig.pepper);
+
+        // the rest of the slots in a new stack are counted as dead
+        let mut new_dead = 0u64;
+        let mut revived = false;
+        let stack = if pond.stack() == 0 {
+            let stack = self.new_stack_id()?;
+            for (idx, x) in buf.iter_mut().enumerate() {
+                let xg = x.gene_mut();
+                xg.id = stack + idx as u64;
+                xg.server = ig.server;
+                *x.pond_mut() = *pond.gene();
+            }
+            ig.id = stack;
+            recycle.first(ig);
+            buf[0] = *item;
+
+            *pond.stack_mut() = stack;
+            *pond.empty_mut() = STACK as u16 - 1;
+            new_dead = STACK as u64 - 1;
+            stack
+        } else {
+            self.item.list(pond.stack(), &mut buf)?;
+
+            let mut found_empty_slot = false;
+            for (x, slot) in buf.iter_mut().enumerate() {
+                let sg = slot.gene();
+                if !slot.entity_flags().is_alive() && !recycle.exhausted(sg) {
+                    revived = sg.id != 0;
+                    let ig = item.gene_mut();
+                    ig.id = pond.stack() + x as u64;
+                    if sg.id != 0 && recycle.next(sg, ig) {
+                        *item.growth_mut() = slot.growth() + 1;
+                    } else {
+                        recycle.first(ig);
+                    }
+                    slot.clone_from(item);
+                    found_empty_slot = true;
+                    *pond.empty_mut() = pond.empty().saturating_sub(1);
+                    // if pond.empty() > 0 {
+                    //     pond.empty -= 1;
+                    // }
+                    break;
+                }
+            }
+            if !found_empty_slot {
+                log::error!("could not found an empty slot for item");
+                return Err(SystemError::PondNoEmptySlotWasFound)?;
+            }
+
+            pond.stack()
+        };
+
+        self.item.write_buf_at(&buf, stack)?;
+        self.item.meta_update(|meta| {
+            meta.live += 1;
+            meta.dead += new_dead;
+            if revived {
+                meta.dead -= 1;
+            }
+        })?;
+
+        Ok(())
     }
 
     pub(super) fn new_stack_id(&mut self) -> Result<GeneId, ShahError> {
@@ -135,7 +341,7 @@ impl<
             return Ok(GeneId(1));
         }
 
-        let sn = Dk::N * PAGE_SIZE as u64;
+        let sn = Dk::N * STACK as u64;
         let usabe = pos - (ENTITY_META + Dk::N);
 
         let (id, offset) = (usabe / sn, usabe % sn);
@@ -143,6 +349,6 @@ impl<
             log::warn!("{} new-stack-id bad offset: {offset}", self.ls);
         }
 
-        Ok(GeneId(id * PAGE_SIZE as u64 + 1))
+        Ok(GeneId(id * STACK as u64 + 1))
     }
 }
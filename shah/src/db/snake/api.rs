@@ -4,12 +4,20 @@ use crate::{
     models::{Binary, Gene},
 };
 use std::io::{Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::FileExt;
 
 impl SnakeDb {
     pub fn write(
         &mut self, gene: &Gene, head: &mut SnakeHead, offset: u64, data: &[u8],
     ) -> Result<(), ShahError> {
-        let len = self.check_offset(gene, head, offset, data.len())?;
+        let mut len = self.check_offset(gene, head, offset, data.len())?;
+        if head.refs > 1 {
+            return Err(SystemError::SnakeIsShared)?;
+        }
+        if head.flags.is_compressed() {
+            self.expand(head, offset + len as u64)?;
+            len = self.check_offset(gene, head, offset, data.len())?;
+        }
 
         // self.file.seek(SeekFrom::Start(head.position + head.capacity - 1))?;
         // self.file.write_all(&[0u8])?;
@@ -25,6 +33,9 @@ impl SnakeDb {
         data: &mut [u8],
     ) -> Result<(), ShahError> {
         let len = self.check_offset(gene, head, offset, data.len())?;
+        if head.flags.is_compressed() {
+            return self.read_packed(head, offset, &mut data[..len]);
+        }
         // log::info!(
         //     "read len: {len} - offset: {offset} - data len: {} - head: {head:#?}",
         //     data.len()
@@ -36,6 +47,10 @@ impl SnakeDb {
         Ok(())
     }
 
+    /// finish a write by setting the length of the content. with dedup
+    /// on, a snake with the same content as another one is freed and
+    /// `head` is the shared one. with compression on, the content is
+    /// compressed when it gets smaller
     pub fn set_length(
         &mut self, gene: &Gene, head: &mut SnakeHead, length: u64,
     ) -> Result<(), ShahError> {
@@ -45,6 +60,15 @@ impl SnakeDb {
         }
         assert!(head.position >= SnakeHead::N);
         assert_ne!(head.capacity, 0);
+        if head.refs > 1 {
+            return Err(SystemError::SnakeIsShared)?;
+        }
+        if head.flags.is_compressed() {
+            if length == head.length {
+                return Ok(());
+            }
+            self.expand(head, length)?;
+        }
         if length > head.capacity {
             log::error!(
                 "{} set_length: bad length: {length} >= {}",
@@ -55,9 +79,11 @@ impl SnakeDb {
         }
 
         head.length = length;
+        head.stored = length;
         self.index.set(head)?;
 
-        Ok(())
+        self.dedup_finish(gene, head)?;
+        self.pack(head)
     }
 
     pub fn free(&mut self, gene: &Gene) -> Result<(), ShahError> {
@@ -72,15 +98,95 @@ impl SnakeDb {
             return Ok(());
         }
 
+        if head.refs > 1 {
+            head.refs -= 1;
+            return self.index.set(&mut head);
+        }
+
+        self.dedup_forget(&head)?;
+        head.capacity = head.region();
+        head.flags.set_is_compressed(false);
         head.flags.set_is_free(true);
         self.index.set(&mut head)?;
-        if let Err(e) = self.add_free(head) {
-            log::warn!("add_free failed in free: {e:?}");
+        if let Err(e) = self.release(head) {
+            log::warn!("release failed in free: {e:?}");
         }
 
         Ok(())
     }
 
+    /// change the capacity of the snake and keep its gene. it grows in
+    /// place into free space or the end of the file, otherwise the data
+    /// is moved. shrinking gives the cut off tail to the free list
+    pub fn realloc(
+        &mut self, gene: &Gene, capacity: u64, head: &mut SnakeHead,
+    ) -> Result<(), ShahError> {
+        if capacity == 0 {
+            log::error!("{} realloc: capacity is zero", self.ls);
+            return Err(SystemError::SnakeCapacityIsZero)?;
+        }
+
+        self.index.get(gene, head)?;
+        if head.flags.is_free() {
+            return Err(NotFound::SnakeIsFree)?;
+        }
+        if head.refs > 1 {
+            return Err(SystemError::SnakeIsShared)?;
+        }
+        if head.flags.is_compressed() {
+            self.expand(head, capacity)?;
+        }
+
+        if capacity < head.capacity {
+            let tail = head.capacity - capacity;
+            head.capacity = capacity;
+            head.length = head.length.min(capacity);
+            head.stored = head.length;
+            self.index.set(head)?;
+            return self.free_range(head.position + capacity, tail);
+        }
+        if capacity == head.capacity {
+            return Ok(());
+        }
+
+        let end = head.position + head.capacity;
+        let extra = capacity - head.capacity;
+        let mut grown = end == self.file_size()?;
+        if !grown && let Some(mut free) = self.take_free_at(end)? {
+            let free_end = free.position + free.capacity;
+            if free.capacity > extra {
+                free.position += extra;
+                free.capacity -= extra;
+                self.add_free(free)?;
+                grown = true;
+            } else if free.capacity == extra || free_end == self.file_size()? {
+                self.index.del(&free.gene, &mut SnakeHead::default())?;
+                grown = true;
+            } else {
+                self.add_free(free)?;
+            }
+        }
+
+        if grown {
+            self.file.write_all_at(&[0u8], head.position + capacity - 1)?;
+            head.capacity = capacity;
+            return self.index.set(head);
+        }
+
+        let region = self.take_region(capacity)?;
+        if region.gene.id != 0 {
+            self.index.del(&region.gene, &mut SnakeHead::default())?;
+        }
+        self.copy(head.position, region.position, head.capacity)?;
+
+        let (position, old) = (head.position, head.capacity);
+        head.position = region.position;
+        head.capacity = region.capacity;
+        self.index.set(head)?;
+
+        self.free_range(position, old)
+    }
+
     pub fn alloc(
         &mut self, capacity: u64, head: &mut SnakeHead,
     ) -> Result<(), ShahError> {
@@ -92,22 +198,12 @@ impl SnakeDb {
         head.zeroed();
         head.entity_flags.set_is_alive(true);
         head.flags.set_is_free(false);
+        head.refs = 1;
 
-        if let Some(free) = self.take_free(capacity)? {
-            head.position = free.position;
-            head.capacity = free.capacity;
-            head.gene = free.gene;
-        } else {
-            head.position = self.file_size()?;
-            if head.position < SnakeHead::N {
-                head.position =
-                    self.file.seek(SeekFrom::Start(SnakeHead::N))?;
-            }
-            head.capacity = capacity;
-        }
-
-        self.file.seek(SeekFrom::Start(head.position + head.capacity - 1))?;
-        self.file.write_all(&[0u8])?;
+        let free = self.take_region(capacity)?;
+        head.position = free.position;
+        head.capacity = free.capacity;
+        head.gene = free.gene;
 
         if let Err(e) = self.index.set(head) {
             e.not_found_ok()?;
@@ -0,0 +1,173 @@
+use super::{SnakeDb, SnakeHead};
+use crate::{BLOCK_SIZE, ShahError, SystemError, lz};
+use std::os::unix::fs::FileExt;
+
+/// bytes of content in a compressed block. reads only decompress the
+/// blocks they touch
+const PACK_BLOCK: u64 = 16 * BLOCK_SIZE as u64;
+
+/// number of compressed blocks of a content
+fn pack_blocks(length: u64) -> u64 {
+    length.div_ceil(PACK_BLOCK)
+}
+
+impl SnakeDb {
+    /// store the content compressed when it gets smaller. set_length runs
+    /// it when compression is on, see [`SnakeDb::set_compress`].
+    /// the region starts with the end of every compressed block as a u32
+    /// and the blocks follow. the capacity stays the same and the region
+    /// is only `stored` bytes. it is written a block at a time into a new
+    /// region and the old one is freed after the head points to it
+    pub(super) fn pack(
+        &mut self, head: &mut SnakeHead,
+    ) -> Result<(), ShahError> {
+        if !self.compress
+            || head.flags.is_compressed()
+            || head.length == 0
+            || head.refs > 1
+        {
+            return Ok(());
+        }
+
+        let count = pack_blocks(head.length);
+        let table = count * 4;
+        let region = self.take_region(table + head.length)?;
+        if region.gene.id != 0 {
+            self.index.del(&region.gene, &mut SnakeHead::default())?;
+        }
+
+        let mut raw = vec![0u8; PACK_BLOCK as usize];
+        let mut ends = Vec::with_capacity(table as usize);
+        let mut end = 0u64;
+        for index in 0..count {
+            let at = index * PACK_BLOCK;
+            let n = (head.length - at).min(PACK_BLOCK) as usize;
+            self.file.read_exact_at(&mut raw[..n], head.position + at)?;
+            let packed = lz::compress(&raw[..n]);
+            // the ends are u32, a bigger content is kept as it is
+            let next = table + end + packed.len() as u64;
+            if next >= head.length || next > u32::MAX as u64 {
+                return self.cut_region(region.position, region.capacity, 0);
+            }
+
+            self.file.write_all_at(&packed, region.position + table + end)?;
+            end += packed.len() as u64;
+            ends.extend_from_slice(&(end as u32).to_le_bytes());
+        }
+        self.file.write_all_at(&ends, region.position)?;
+
+        let (position, capacity) = (head.position, head.capacity);
+        head.flags.set_is_compressed(true);
+        head.stored = table + end;
+        head.position = region.position;
+        self.index.set(head)?;
+
+        self.cut_region(region.position, region.capacity, head.stored)?;
+        self.free_range(position, capacity)
+    }
+
+    /// store a compressed content uncompressed in a region of at least
+    /// `capacity` bytes
+    pub(super) fn expand(
+        &mut self, head: &mut SnakeHead, capacity: u64,
+    ) -> Result<(), ShahError> {
+        let capacity = capacity.max(head.length).max(head.capacity);
+        let region = self.take_region(capacity)?;
+        if region.gene.id != 0 {
+            self.index.del(&region.gene, &mut SnakeHead::default())?;
+        }
+
+        for index in 0..pack_blocks(head.length) {
+            let raw = self.unpack(head, index)?;
+            let at = region.position + index * PACK_BLOCK;
+            self.file.write_all_at(&raw, at)?;
+        }
+
+        let (position, old) = (head.position, head.region());
+        head.flags.set_is_compressed(false);
+        head.stored = head.length;
+        head.position = region.position;
+        head.capacity = region.capacity;
+        self.index.set(head)?;
+
+        self.free_range(position, old)
+    }
+
+    /// read from a compressed content. past the length is zeros
+    pub(super) fn read_packed(
+        &mut self, head: &SnakeHead, offset: u64, data: &mut [u8],
+    ) -> Result<(), ShahError> {
+        let mut done = 0;
+        while done < data.len() {
+            let at = offset + done as u64;
+            if at >= head.length {
+                data[done..].fill(0);
+                break;
+            }
+
+            let index = at / PACK_BLOCK;
+            let raw = self.unpack(head, index)?;
+            let start = (at - index * PACK_BLOCK) as usize;
+            let n = (raw.len() - start).min(data.len() - done);
+            data[done..done + n].copy_from_slice(&raw[start..start + n]);
+            done += n;
+        }
+        Ok(())
+    }
+
+    /// call `f` with every block of a compressed content, in order
+    pub(super) fn each_packed(
+        &mut self, head: &SnakeHead, mut f: impl FnMut(&[u8]),
+    ) -> Result<(), ShahError> {
+        for index in 0..pack_blocks(head.length) {
+            f(&self.unpack(head, index)?);
+        }
+        Ok(())
+    }
+
+    /// the content of the compressed block `index`
+    fn unpack(
+        &mut self, head: &SnakeHead, index: u64,
+    ) -> Result<Vec<u8>, ShahError> {
+        let table = pack_blocks(head.length) * 4;
+        let mut ends = [0u8; 8];
+        if index == 0 {
+            self.file.read_exact_at(&mut ends[4..], head.position)?;
+        } else {
+            let at = head.position + (index - 1) * 4;
+            self.file.read_exact_at(&mut ends, at)?;
+        }
+        let start = u32::from_le_bytes([ends[0], ends[1], ends[2], ends[3]]);
+        let end = u32::from_le_bytes([ends[4], ends[5], ends[6], ends[7]]);
+        let size = (head.length - index * PACK_BLOCK).min(PACK_BLOCK) as usize;
+
+        let raw = if start <= end && table + end as u64 <= head.stored {
+            let mut packed = vec![0u8; (end - start) as usize];
+            let at = head.position + table + start as u64;
+            self.file.read_exact_at(&mut packed, at)?;
+            lz::decompress(&packed, size)
+        } else {
+            None
+        };
+        let Some(raw) = raw else {
+            log::error!("{} bad compressed snake: {:?}", self.ls, head.gene);
+            return Err(SystemError::SnakeBadCompression)?;
+        };
+        Ok(raw)
+    }
+
+    /// keep the first `keep` bytes of the region and free the rest. at
+    /// the end of the file the rest is cut off
+    fn cut_region(
+        &mut self, position: u64, capacity: u64, keep: u64,
+    ) -> Result<(), ShahError> {
+        if keep >= capacity {
+            return Ok(());
+        }
+        if position + capacity == self.file_size()? {
+            self.file.set_len(position + keep)?;
+            return Ok(());
+        }
+        self.free_range(position + keep, capacity - keep)
+    }
+}
@@ -0,0 +1,165 @@
+use super::{SnakeDb, SnakeHead};
+use crate::db::trie::{Trie, TrieAbc, TrieKey};
+use crate::hash::{SHA256_LEN, Sha256, sha256};
+use crate::models::Gene;
+use crate::{BLOCK_SIZE, OptNotFound, ShahError, SystemError};
+use std::os::unix::fs::FileExt;
+
+type Hash = [u8; SHA256_LEN];
+
+/// bytes of the content hash used as the trie key. the first snake
+/// with a key keeps it, another content with the same key is not shared
+const DEDUP_KEY: usize = 8;
+
+#[derive(Debug)]
+pub(super) struct SnakeHashAbc;
+
+impl TrieAbc for SnakeHashAbc {
+    type Item<'a> = &'a Hash;
+    const ABC: &'static str = "0123456789abcdef";
+
+    fn convert(&self, key: Self::Item<'_>) -> Result<TrieKey, ShahError> {
+        let mut tk = TrieKey::new(DEDUP_KEY * 2 - 1);
+        tk.root = (key[0] >> 4) as usize;
+        tk.tree.push((key[0] & 15) as usize);
+        for b in key[1..DEDUP_KEY].iter() {
+            tk.tree.push((b >> 4) as usize);
+            tk.tree.push((b & 15) as usize);
+        }
+        Ok(tk)
+    }
+}
+
+pub(super) type SnakeDedupDb =
+    Trie<{ SnakeHashAbc::ABC.len() }, SnakeHashAbc, Gene>;
+
+/// what the dedup index has for a content hash
+enum DedupSlot {
+    /// an alive snake with the same content
+    Found(SnakeHead),
+    /// an alive snake with other content whose hash has the same key
+    Taken,
+    /// nothing, or a snake that is gone or was written since
+    Free,
+}
+
+impl SnakeDb {
+    /// alloc a snake with `data` or share an existing snake with the
+    /// same content. shared snakes are read only and every owner must
+    /// call free once
+    pub fn alloc_dedup(
+        &mut self, data: &[u8], head: &mut SnakeHead,
+    ) -> Result<(), ShahError> {
+        let hash = sha256(data);
+        if let DedupSlot::Found(mut shared) = self.dedup_slot(&hash)? {
+            shared.refs += 1;
+            self.index.set(&mut shared)?;
+            *head = shared;
+            return Ok(());
+        }
+
+        self.alloc(data.len() as u64, head)?;
+        let gene = head.gene;
+        self.write(&gene, head, 0, data)?;
+        self.set_length(&gene, head, data.len() as u64)
+    }
+
+    /// run by set_length. when a snake with the same content exists this
+    /// one is freed and `head` is the shared one
+    pub(super) fn dedup_finish(
+        &mut self, gene: &Gene, head: &mut SnakeHead,
+    ) -> Result<(), ShahError> {
+        if self.dedup.is_none() || head.refs > 1 {
+            return Ok(());
+        }
+
+        let hash = self.content_hash(head)?;
+        match self.dedup_slot(&hash)? {
+            DedupSlot::Found(shared) if shared.gene == *gene => Ok(()),
+            DedupSlot::Found(mut shared) => {
+                shared.refs += 1;
+                self.index.set(&mut shared)?;
+                self.free(gene)?;
+                *head = shared;
+                Ok(())
+            }
+            DedupSlot::Taken => {
+                log::debug!("{} dedup key is taken: {gene:?}", self.ls);
+                Ok(())
+            }
+            DedupSlot::Free => {
+                let Some(db) = self.dedup.as_mut() else { return Ok(()) };
+                let key = db.key(&hash)?;
+                db.set(&key, *gene)?;
+                Ok(())
+            }
+        }
+    }
+
+    /// drop the index entry of a snake that is being freed
+    pub(super) fn dedup_forget(
+        &mut self, head: &SnakeHead,
+    ) -> Result<(), ShahError> {
+        if self.dedup.is_none() {
+            return Ok(());
+        }
+
+        let hash = self.content_hash(head)?;
+        let Some(db) = self.dedup.as_mut() else { return Ok(()) };
+        let key = db.key(&hash)?;
+        if db.get(&key).onf()? == Some(head.gene) {
+            db.del(&key)?;
+        }
+        Ok(())
+    }
+
+    /// the snake recorded for the key of `hash`
+    fn dedup_slot(&mut self, hash: &Hash) -> Result<DedupSlot, ShahError> {
+        let Some(db) = self.dedup.as_mut() else { return Ok(DedupSlot::Free) };
+        let key = db.key(hash)?;
+        let Some(gene) = db.get(&key).onf()? else {
+            return Ok(DedupSlot::Free);
+        };
+        if gene.id == 0 {
+            return Ok(DedupSlot::Free);
+        }
+
+        let mut head = SnakeHead::default();
+        if self.index.get(&gene, &mut head).onf()?.is_none()
+            || head.flags.is_free()
+        {
+            return Ok(DedupSlot::Free);
+        }
+
+        let found = self.content_hash(&head)?;
+        if found == *hash {
+            Ok(DedupSlot::Found(head))
+        } else if found[..DEDUP_KEY] == hash[..DEDUP_KEY] {
+            Ok(DedupSlot::Taken)
+        } else {
+            Ok(DedupSlot::Free)
+        }
+    }
+
+    fn content_hash(&mut self, head: &SnakeHead) -> Result<Hash, ShahError> {
+        if head.flags.is_compressed() {
+            let mut sha = Sha256::new();
+            self.each_packed(head, |raw| sha.update(raw))?;
+            return Ok(sha.finish());
+        }
+        if head.length > head.capacity {
+            return Err(SystemError::SnakeBadLength)?;
+        }
+
+        let mut sha = Sha256::new();
+        let mut buf = [0u8; BLOCK_SIZE];
+        let mut done = 0u64;
+        while done < head.length {
+            let n = (head.length - done).min(BLOCK_SIZE as u64) as usize;
+            self.file.read_exact_at(&mut buf[..n], head.position + done)?;
+            sha.update(&buf[..n]);
+            done += n as u64;
+        }
+        Ok(sha.finish())
+    }
+}
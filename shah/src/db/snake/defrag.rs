@@ -0,0 +1,109 @@
+use super::{SnakeDb, SnakeHead};
+use crate::models::{GeneId, Performed};
+use crate::{OptNotFound, PAGE_SIZE, ShahError};
+
+impl SnakeDb {
+    /// move live snakes into free space before them so the free space
+    /// ends up at the tail of the file, where it is cut off
+    pub(super) fn work_defrag(&mut self) -> Result<Performed, ShahError> {
+        if !self.defrag {
+            return Ok(Performed(false));
+        }
+        if self.trim_tail()? {
+            return Ok(Performed(true));
+        }
+
+        let mut page = [SnakeHead::default(); PAGE_SIZE];
+        let id = self.defrag_cursor;
+        let Some(len) = self.index.list(id, &mut page).onf()? else {
+            self.defrag_cursor = GeneId(1);
+            return Ok(Performed(false));
+        };
+        self.defrag_cursor =
+            if len < PAGE_SIZE { GeneId(1) } else { id + len as u64 };
+
+        let mut performed = false;
+        for head in page[..len].iter_mut() {
+            if head.gene.id == 0
+                || !head.entity_flags.is_alive()
+                || head.flags.is_free()
+            {
+                continue;
+            }
+            performed |= self.defrag_move(head)?;
+        }
+
+        Ok(Performed(performed))
+    }
+
+    /// take the free region that ends right where `position` starts
+    fn free_before(
+        &mut self, position: u64, capacity: u64,
+    ) -> Result<Option<SnakeHead>, ShahError> {
+        match self.free_ending_at(position)? {
+            Some(free) if free.capacity >= capacity => {
+                self.take_free_head(&free)?;
+                Ok(Some(free))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// move the snake toward the head of the file. it slides into the
+    /// free region right before it, or else into the best fitting free
+    /// region anywhere before it. the region must fit the whole snake,
+    /// so the old copy is never overwritten and a crash in the middle
+    /// loses nothing
+    fn defrag_move(&mut self, head: &mut SnakeHead) -> Result<bool, ShahError> {
+        let (position, size) = (head.position, head.region());
+        if let Some(mut free) = self.free_before(position, size)? {
+            self.copy(position, free.position, size)?;
+            head.position = free.position;
+            self.index.set(head)?;
+
+            free.position += size;
+            self.release(free)?;
+        } else {
+            let Some(fit) = self.free_list.best_fit_before(size, position)?
+            else {
+                return Ok(false);
+            };
+            let Some(mut free) = self.take_listed(fit)? else {
+                return Ok(false);
+            };
+
+            self.copy(position, free.position, size)?;
+            head.position = free.position;
+            self.index.set(head)?;
+
+            if free.capacity > size {
+                free.position += size;
+                free.capacity -= size;
+                self.release(free)?;
+            } else {
+                self.index.del(&free.gene, &mut SnakeHead::default())?;
+            }
+            self.free_range(position, size)?;
+        }
+
+        log::debug!(
+            "{} moved {:?} from {position} to {}",
+            self.ls,
+            head.gene,
+            head.position
+        );
+        Ok(true)
+    }
+
+    /// cut the free region at the end of the file off
+    fn trim_tail(&mut self) -> Result<bool, ShahError> {
+        let Some(free) = self.free_last()? else { return Ok(false) };
+        self.take_free_head(&free)?;
+
+        self.file.set_len(free.position)?;
+        self.index.del(&free.gene, &mut SnakeHead::default())?;
+        log::info!("{} trimmed {} bytes off the tail", self.ls, free.capacity);
+
+        Ok(true)
+    }
+}
@@ -1,261 +1,486 @@
-use super::{FREE_LIST_SIZE, SnakeDb, SnakeFree, TCD};
-use crate::{ShahError, db::snake::SnakeHead, models::Gene};
+use super::{SnakeDb, SnakeFree, TCD};
+use crate::db::snake::SnakeHead;
+use crate::models::{Binary, Gene, GeneId, Performed};
+use crate::{BLOCK_SIZE, OptNotFound, PAGE_SIZE, ShahError, utils};
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+/// number of size classes. a region of `n` bytes is in class `ilog2(n)`
+const CLASSES: usize = 64;
+/// how many free regions of a class a best fit looks at
+const FIT_SCAN: usize = 64;
+/// a free region of at least this many bytes has its gene at both ends
+/// in the data file, so the regions around it can find it to merge
+const TAGGED: u64 = 2 * Gene::N;
+
+/// the first free region of every size class
+#[crate::model]
+#[derive(Debug)]
+struct SnakeFreeTable {
+    count: u64,
+    bytes: u64,
+    head: [Gene; CLASSES],
+}
 
-impl SnakeDb {
-    pub(super) fn take_free(
-        &mut self, capacity: u64,
-    ) -> Result<Option<SnakeFree>, ShahError> {
-        let db_size = self.file_size()?;
+/// the links of a free region in its size class, one record per gene id
+#[crate::model]
+#[derive(Debug)]
+struct SnakeFreeLink {
+    gene: Gene,
+    next: Gene,
+    past: Gene,
+    position: u64,
+    capacity: u64,
+}
 
-        let mut travel = 0;
-        for opt_free in self.free_list.iter_mut() {
-            if travel >= self.free {
-                break;
-            }
-            let Some(free) = opt_free else { continue };
-            travel += 1;
-
-            assert_ne!(free.position, 0);
-            assert_ne!(free.capacity, 0);
-            assert_ne!(free.gene.id, 0);
-
-            if free.position + free.capacity == db_size {
-                if free.capacity > capacity + TCD {
-                    let val = SnakeFree {
-                        position: free.position,
-                        capacity,
-                        gene: Gene::default(),
-                    };
-
-                    let mut disk = SnakeHead::default();
-                    self.index.get(&free.gene, &mut disk)?;
-                    if !disk.flags.is_free() {
-                        log::warn!("free is not even free on the disk :/ wtf");
-                        return Ok(None);
-                    }
-                    if disk.capacity != free.capacity
-                        || disk.position != free.position
-                    {
-                        log::warn!(
-                            "invalid snake free_list: {disk:?} != {free:?}"
-                        );
-                        return Ok(None);
-                    }
-
-                    free.position += capacity;
-                    free.capacity -= capacity;
-
-                    disk.position = free.position;
-                    disk.capacity = free.capacity;
-                    self.index.set(&mut disk)?;
-
-                    return Ok(Some(val));
-                }
+fn size_class(capacity: u64) -> usize {
+    capacity.max(1).ilog2() as usize
+}
 
-                let val = SnakeFree {
-                    position: free.position,
-                    capacity: free.capacity.max(capacity),
-                    gene: free.gene,
-                };
+/// free regions of the snake file in a list per size class, kept in
+/// `free.snake.shah` next to the index. the table of classes is read at
+/// open, so nothing is scanned. regions are merged through the genes at
+/// their ends, see [`TAGGED`].
+/// after a crash in the middle of an update a list can be cut short.
+/// the regions cut off are still free in the index and come back when
+/// a region next to them is freed
+#[derive(Debug)]
+pub(super) struct SnakeFreeList {
+    file: File,
+    table: SnakeFreeTable,
+}
 
-                if self.free > 0 {
-                    self.free -= 1;
-                }
-                *opt_free = None;
-                return Ok(Some(val));
+impl SnakeFreeList {
+    /// open the list. true when it is new and has to be filled from the
+    /// index, see [`SnakeDb::work_free_scan`]
+    pub(super) fn new(data_path: &Path) -> Result<(Self, bool), ShahError> {
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(data_path.join("free.snake.shah"))?;
+
+        let mut list = Self { file, table: SnakeFreeTable::default() };
+        let fresh = list.file.metadata()?.len() < SnakeFreeTable::N;
+        if fresh {
+            list.table_set()?;
+        } else {
+            list.file.read_exact_at(list.table.as_binary_mut(), 0)?;
+        }
+        Ok((list, fresh))
+    }
+
+    pub(super) fn len(&self) -> u64 {
+        self.table.count
+    }
+
+    pub(super) fn bytes(&self) -> u64 {
+        self.table.bytes
+    }
+
+    fn table_set(&self) -> Result<(), ShahError> {
+        self.file.write_all_at(self.table.as_binary(), 0)?;
+        Ok(())
+    }
+
+    fn link(&self, gene: &Gene) -> Result<SnakeFreeLink, ShahError> {
+        let mut link = SnakeFreeLink::default();
+        let pos = SnakeFreeTable::N + gene.id.0 * SnakeFreeLink::N;
+        match self.file.read_exact_at(link.as_binary_mut(), pos) {
+            Ok(()) => Ok(link),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                Ok(SnakeFreeLink::default())
             }
+            Err(e) => Err(e)?,
+        }
+    }
 
-            if free.capacity < capacity {
-                continue;
+    fn link_set(
+        &self, id: GeneId, link: &SnakeFreeLink,
+    ) -> Result<(), ShahError> {
+        let pos = SnakeFreeTable::N + id.0 * SnakeFreeLink::N;
+        self.file.write_all_at(link.as_binary(), pos)?;
+        Ok(())
+    }
+
+    /// put the region at the front of its class
+    fn insert(&mut self, free: SnakeFree) -> Result<(), ShahError> {
+        let class = size_class(free.capacity);
+        let next = self.table.head[class];
+        let link = SnakeFreeLink {
+            gene: free.gene,
+            next,
+            past: Gene::NONE,
+            position: free.position,
+            capacity: free.capacity,
+        };
+        self.link_set(free.gene.id, &link)?;
+
+        let mut temp = self.link(&next)?;
+        if next.is_some() && temp.gene == next {
+            temp.past = free.gene;
+            self.link_set(next.id, &temp)?;
+        }
+
+        self.table.head[class] = free.gene;
+        self.table.count += 1;
+        self.table.bytes += free.capacity;
+        self.table_set()
+    }
+
+    /// take the region out of its class. false when it was not listed
+    fn remove(&mut self, gene: &Gene) -> Result<bool, ShahError> {
+        let link = self.link(gene)?;
+        if gene.is_none() || link.gene != *gene {
+            return Ok(false);
+        }
+
+        let class = size_class(link.capacity);
+        if link.past.is_some() {
+            let mut temp = self.link(&link.past)?;
+            if temp.gene == link.past && temp.next == *gene {
+                temp.next = link.next;
+                self.link_set(link.past.id, &temp)?;
+            }
+        } else if self.table.head[class] == *gene {
+            self.table.head[class] = link.next;
+        }
+        if link.next.is_some() {
+            let mut temp = self.link(&link.next)?;
+            if temp.gene == link.next && temp.past == *gene {
+                temp.past = link.past;
+                self.link_set(link.next.id, &temp)?;
             }
+        }
+        self.link_set(gene.id, &SnakeFreeLink::default())?;
 
-            if free.capacity - capacity < TCD {
-                if self.free > 0 {
-                    self.free -= 1;
+        self.table.count = self.table.count.saturating_sub(1);
+        self.table.bytes = self.table.bytes.saturating_sub(link.capacity);
+        self.table_set()?;
+        Ok(true)
+    }
+
+    /// the smallest region of at least `capacity` bytes that starts
+    /// before `before`. the classes are looked at from the smallest one
+    /// that may fit and only the first [`FIT_SCAN`] regions of each,
+    /// so a long class is never walked
+    fn fit(
+        &self, capacity: u64, before: u64,
+    ) -> Result<Option<SnakeFree>, ShahError> {
+        let mut best: Option<SnakeFree> = None;
+        for class in size_class(capacity)..CLASSES {
+            let mut gene = self.table.head[class];
+            for _ in 0..FIT_SCAN {
+                if gene.is_none() {
+                    break;
                 }
-                let val = *opt_free;
-                *opt_free = None;
-                return Ok(val);
+                let link = self.link(&gene)?;
+                if link.gene != gene {
+                    break;
+                }
+                if link.capacity >= capacity
+                    && link.position < before
+                    && best.is_none_or(|b| link.capacity < b.capacity)
+                {
+                    best = Some(SnakeFree {
+                        gene,
+                        position: link.position,
+                        capacity: link.capacity,
+                    });
+                }
+                gene = link.next;
+            }
+            // every region of a bigger class is bigger than this one
+            if best.is_some() {
+                break;
             }
+        }
+        Ok(best)
+    }
+
+    /// the smallest region of at least `capacity` bytes
+    fn best_fit(&self, capacity: u64) -> Result<Option<SnakeFree>, ShahError> {
+        self.fit(capacity, u64::MAX)
+    }
 
-            let mut disk = SnakeHead::default();
-            self.index.get(&free.gene, &mut disk)?;
-            if !disk.flags.is_free() {
-                log::warn!("free is not even free :/ wtf");
-                return Ok(None);
+    /// the smallest region of at least `capacity` bytes that starts
+    /// before `position`
+    pub(super) fn best_fit_before(
+        &self, capacity: u64, position: u64,
+    ) -> Result<Option<SnakeFree>, ShahError> {
+        self.fit(capacity, position)
+    }
+}
+
+impl SnakeDb {
+    /// fill a new free list with the free heads of the index, a page per
+    /// call. it only runs when the free list file is new and the index is
+    /// not, like a db from before the file. until it is done the regions
+    /// that are not found yet are not used
+    pub(super) fn work_free_scan(&mut self) -> Result<Performed, ShahError> {
+        if self.free_scan == 0 {
+            return Ok(Performed(false));
+        }
+
+        let mut page = [SnakeHead::default(); PAGE_SIZE];
+        let id = self.free_scan;
+        let Some(len) = self.index.list(id, &mut page).onf()? else {
+            self.free_scan = GeneId(0);
+            return Ok(Performed(false));
+        };
+
+        let mut disk = SnakeHead::default();
+        for head in page[..len].iter() {
+            if head.gene.id == 0
+                || !head.entity_flags.is_alive()
+                || !head.flags.is_free()
+            {
+                continue;
             }
-            if disk.capacity != free.capacity || disk.position != free.position
+            // an earlier head of this page may have been merged with it
+            if self.index.get(&head.gene, &mut disk).onf()?.is_none()
+                || !disk.flags.is_free()
             {
-                log::warn!("invalid snake free_list: {disk:?} != {free:?}");
-                return Ok(None);
+                continue;
             }
+            self.add_free(disk)?;
+        }
 
-            disk.capacity -= capacity;
-            self.index.set(&mut disk)?;
-            free.capacity -= capacity;
-            return Ok(Some(SnakeFree {
-                gene: Default::default(),
-                position: free.position + free.capacity,
-                capacity,
-            }));
+        self.free_scan = id + len as u64;
+        if len < PAGE_SIZE {
+            log::info!("{} free regions: {}", self.ls, self.free_list.len());
+            self.free_scan = GeneId(0);
         }
 
-        Ok(None)
+        Ok(Performed(true))
     }
 
-    pub(super) fn add_free(
-        &mut self, mut head: SnakeHead,
-    ) -> Result<(), ShahError> {
-        if head.position == 0 || head.capacity == 0 || head.gene.id == 0 {
-            log::warn!("invalid head into add_free");
-            return Ok(());
+    /// the free head with this gene, none when it is not free anymore
+    fn free_head(
+        &mut self, gene: &Gene,
+    ) -> Result<Option<SnakeHead>, ShahError> {
+        // the gene may come from the bytes of a snake, so check it first
+        let mut one = [SnakeHead::default()];
+        if gene.id == 0
+            || gene.id > self.index.count()?.total
+            || self.index.list(gene.id, &mut one).onf()?.is_none()
+        {
+            return Ok(None);
         }
+        let head = one[0];
+        let free = head.gene == *gene
+            && head.entity_flags.is_alive()
+            && head.flags.is_free();
+        Ok(free.then_some(head))
+    }
 
-        let mut index = if self.free == 0 { 0 } else { FREE_LIST_SIZE };
-        let mut travel = 0;
-        // let mut round_two = false;
-        // let mut round_two_index = 0usize;
-        // log::warn!("add_free: {} | {}", head.gene.id, self.free);
+    /// the free head of the gene that is written at `at` in the data file
+    fn free_tag(&mut self, at: u64) -> Result<Option<SnakeHead>, ShahError> {
+        let mut gene = Gene::default();
+        match self.file.read_exact_at(gene.as_binary_mut(), at) {
+            Ok(()) => self.free_head(&gene),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e)?,
+        }
+    }
 
-        let mut fdx = 0usize;
-        while fdx < FREE_LIST_SIZE {
-            if travel > self.free {
-                break;
-            }
-            let slot = &mut self.free_list[fdx];
-            let Some(free) = slot else {
-                if index == FREE_LIST_SIZE {
-                    index = fdx;
-                }
-                fdx += 1;
-                continue;
-            };
-            travel += 1;
-
-            assert_ne!(free.position, 0);
-            assert_ne!(free.capacity, 0);
-            assert_ne!(free.gene.id, 0);
-
-            // log::trace!(
-            //     "[{}] head: {} + {} == {}",
-            //     head.gene.id,
-            //     head.position,
-            //     head.capacity,
-            //     head.position + head.capacity
-            // );
-            // log::trace!(
-            //     "[{}] free: {} + {} == {}",
-            //     free.gene.id,
-            //     free.position,
-            //     free.capacity,
-            //     free.position + free.capacity
-            // );
-            // log::trace!("round_two: {round_two}");
-
-            if free.position + free.capacity == head.position {
-                // log::info!("found before: round two: {round_two}");
-                let mut disk = SnakeHead::default();
-                self.index.get(&free.gene, &mut disk)?;
-
-                if disk.capacity != free.capacity
-                    || disk.position != free.position
-                {
-                    log::warn!("disk != free: {disk:?} - {free:?}");
-                    return Ok(());
-                }
+    /// write the gene of a free region at both of its ends
+    fn free_tag_set(&mut self, head: &SnakeHead) -> Result<(), ShahError> {
+        if head.capacity >= TAGGED {
+            let end = head.position + head.capacity - Gene::N;
+            self.file.write_all_at(head.gene.as_binary(), head.position)?;
+            self.file.write_all_at(head.gene.as_binary(), end)?;
+        }
+        Ok(())
+    }
 
-                head.position = free.position;
-                head.capacity += free.capacity;
+    /// the free region that starts at `position`
+    fn free_at(
+        &mut self, position: u64,
+    ) -> Result<Option<SnakeHead>, ShahError> {
+        let head = self.free_tag(position)?;
+        Ok(head.filter(|h| h.position == position && h.capacity >= TAGGED))
+    }
 
-                self.index.del(&disk.gene, &mut SnakeHead::default())?;
-                *slot = None;
-                if self.free > 0 {
-                    self.free -= 1;
-                }
+    /// the free region that ends where `position` starts
+    pub(super) fn free_ending_at(
+        &mut self, position: u64,
+    ) -> Result<Option<SnakeHead>, ShahError> {
+        if position < SnakeHead::N + TAGGED {
+            return Ok(None);
+        }
+        let head = self.free_tag(position - Gene::N)?;
+        Ok(head.filter(|h| {
+            h.position + h.capacity == position && h.capacity >= TAGGED
+        }))
+    }
 
-                // round_two = true;
-                fdx = 0;
-                travel = 0;
-                continue;
+    /// the free region at the end of the file
+    pub(super) fn free_last(&mut self) -> Result<Option<SnakeHead>, ShahError> {
+        let size = self.file_size()?;
+        self.free_ending_at(size)
+    }
 
-                // old.capacity += head.capacity;
-                // free.capacity += head.capacity;
-                // if let Err(e) = self.index.set(&old) {
-                //     log::warn!("set old free: {e:?} - {:?}", free.gene);
-                //     return;
-                // }
-                // if let Err(e) =
-                //     self.index.del(&head.gene, &mut SnakeHead::default())
-                // {
-                //     log::warn!("del head: {e:?} - {:?}", head.gene);
-                //     return;
-                // }
-                //
-                // log::info!("go for round two");
-                // round_two = true;
-                // fdx = 0;
-                // head.position = free.position;
-                // head.capacity = free.capacity;
-                // head.gene = free.gene;
-                // travel = 0;
-                // continue;
-            }
+    /// take the free region out of the list. it is free in the index
+    /// until the caller uses or deletes it
+    pub(super) fn take_free_head(
+        &mut self, head: &SnakeHead,
+    ) -> Result<(), ShahError> {
+        self.free_list.remove(&head.gene)?;
+        Ok(())
+    }
 
-            if head.position + head.capacity == free.position {
-                // log::info!("found after : round two: {round_two}");
-                let mut disk = SnakeHead::default();
-                self.index.get(&free.gene, &mut disk)?;
+    /// take the free region at `position` out of the list
+    pub(super) fn take_free_at(
+        &mut self, position: u64,
+    ) -> Result<Option<SnakeHead>, ShahError> {
+        let Some(head) = self.free_at(position)? else { return Ok(None) };
+        self.take_free_head(&head)?;
+        Ok(Some(head))
+    }
 
-                if disk.capacity != free.capacity
-                    || disk.position != free.position
-                {
-                    log::warn!("disk != free: {disk:?} - {free:?}");
-                    return Ok(());
+    /// take a region the list gave out of it. a stale one is dropped
+    pub(super) fn take_listed(
+        &mut self, free: SnakeFree,
+    ) -> Result<Option<SnakeHead>, ShahError> {
+        self.free_list.remove(&free.gene)?;
+        let head = self.free_head(&free.gene)?.filter(|h| {
+            h.position == free.position && h.capacity == free.capacity
+        });
+        if head.is_none() {
+            log::warn!("{} invalid snake free_list: {free:?}", self.ls);
+        }
+        Ok(head)
+    }
+
+    /// the best fitting free region for `capacity` bytes. the region at
+    /// the end of the file is taken when it is smaller and grown
+    pub(super) fn take_free(
+        &mut self, capacity: u64,
+    ) -> Result<Option<SnakeFree>, ShahError> {
+        let disk = match self.free_list.best_fit(capacity)? {
+            Some(free) => self.take_listed(free)?,
+            None => match self.free_last()? {
+                Some(head) => {
+                    self.take_free_head(&head)?;
+                    Some(head)
                 }
+                None => None,
+            },
+        };
+        let Some(mut disk) = disk else { return Ok(None) };
 
-                head.capacity += free.capacity;
+        if disk.capacity < capacity + TCD {
+            return Ok(Some(SnakeFree {
+                gene: disk.gene,
+                position: disk.position,
+                capacity: disk.capacity.max(capacity),
+            }));
+        }
 
-                self.index.del(&disk.gene, &mut SnakeHead::default())?;
-                *slot = None;
-                if self.free > 0 {
-                    self.free -= 1;
-                }
+        let val = SnakeFree {
+            gene: Gene::default(),
+            position: disk.position,
+            capacity,
+        };
+        disk.position += capacity;
+        disk.capacity -= capacity;
+        self.index.set(&mut disk)?;
+        self.free_list_add(&disk)?;
+
+        Ok(Some(val))
+    }
 
-                // free.position = head.position;
-                // free.capacity = head.capacity;
-                // free.gene = head.gene;
-                // self.index.set(&head)?;
-                // head.position = free.position;
-                // head.capacity = free.capacity;
+    /// tag the region and put it in the list
+    fn free_list_add(&mut self, head: &SnakeHead) -> Result<(), ShahError> {
+        self.free_tag_set(head)?;
+        self.free_list.insert(SnakeFree::from(head))
+    }
 
-                // round_two = true;
-                fdx = 0;
-                travel = 0;
-                continue;
-            }
+    /// a region of at least `capacity` bytes from the free list or the
+    /// end of the file. the gene is set when a whole free head was taken
+    pub(super) fn take_region(
+        &mut self, capacity: u64,
+    ) -> Result<SnakeFree, ShahError> {
+        let free = match self.take_free(capacity)? {
+            Some(free) => free,
+            None => SnakeFree {
+                gene: Gene::default(),
+                position: self.file_size()?.max(SnakeHead::N),
+                capacity,
+            },
+        };
 
-            fdx += 1;
+        self.file.write_all_at(&[0u8], free.position + free.capacity - 1)?;
+        Ok(free)
+    }
+
+    /// give a region that does not belong to any snake to the free list
+    pub(super) fn free_range(
+        &mut self, position: u64, capacity: u64,
+    ) -> Result<(), ShahError> {
+        let mut head = SnakeHead { position, capacity, ..Default::default() };
+        head.flags.set_is_free(true);
+        self.index.add(&mut head)?;
+        self.release(head)
+    }
+
+    /// add the region to the free list, merged with the free regions
+    /// around it. returns the merged region or none when it was not added
+    pub(super) fn add_free(
+        &mut self, mut head: SnakeHead,
+    ) -> Result<Option<SnakeHead>, ShahError> {
+        if head.position == 0 || head.capacity == 0 || head.gene.id == 0 {
+            log::warn!("invalid head into add_free");
+            return Ok(None);
+        }
+
+        if self.free_list.remove(&head.gene)? {
+            log::debug!("{} head was in the free list: {head:?}", self.ls);
+        }
+
+        if let Some(disk) = self.free_ending_at(head.position)? {
+            self.take_free_head(&disk)?;
+            head.position = disk.position;
+            head.capacity += disk.capacity;
+            self.index.del(&disk.gene, &mut SnakeHead::default())?;
+        }
+
+        let end = head.position + head.capacity;
+        if let Some(disk) = self.take_free_at(end)? {
+            head.capacity += disk.capacity;
+            self.index.del(&disk.gene, &mut SnakeHead::default())?;
         }
 
         head.flags.set_is_free(true);
         head.entity_flags.set_is_alive(true);
         self.index.set(&mut head)?;
+        self.free_list_add(&head)?;
 
-        // log::error!("setting a free at: {index} | round_two: {round_two}");
-        if index < FREE_LIST_SIZE {
-            let opt_free = &mut self.free_list[index];
-            if opt_free.is_some() {
-                log::warn!("invalid free index. item space occupied: {index}");
-                return Ok(());
-            }
-            *opt_free = Some(SnakeFree {
-                position: head.position,
-                capacity: head.capacity,
-                gene: head.gene,
-            });
-            self.free += 1;
+        Ok(Some(head))
+    }
+
+    /// free the region and give its disk blocks back to the filesystem
+    /// when it is big enough. see [`SnakeDb::set_punch_hole`]
+    pub(super) fn release(&mut self, head: SnakeHead) -> Result<(), ShahError> {
+        let Some(head) = self.add_free(head)? else { return Ok(()) };
+        if self.punch == 0 {
+            return Ok(());
         }
 
+        // the genes at the ends are kept
+        let bs = BLOCK_SIZE as u64;
+        let start = (head.position + Gene::N).next_multiple_of(bs);
+        let end = (head.position + head.capacity - Gene::N) / bs * bs;
+        if end <= start || end - start < self.punch {
+            return Ok(());
+        }
+
+        if let Err(e) = utils::punch_hole(&self.file, start, end - start) {
+            log::warn!("{} punch hole failed: {e:?}", self.ls);
+        }
         Ok(())
     }
 }
@@ -0,0 +1,214 @@
+use super::{SnakeDb, SnakeHead};
+use crate::models::Gene;
+use crate::{BLOCK_SIZE, ClientError, IsNotFound, ShahError, Taker};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+
+impl SnakeDb {
+    pub fn open(&mut self, gene: &Gene) -> Result<SnakeHandle<'_>, ShahError> {
+        let mut head = SnakeHead::default();
+        self.check_offset(gene, &mut head, 0, 0)?;
+        let length = data_end(&head);
+        Ok(SnakeHandle { db: self, head, pos: 0, length, dirty: false })
+    }
+}
+
+/// the end of the data. a zero length is an empty snake
+fn data_end(head: &SnakeHead) -> u64 {
+    if head.flags.is_compressed() {
+        head.length
+    } else {
+        head.length.min(head.capacity)
+    }
+}
+
+/// where writes stop. a compressed snake is stored uncompressed again
+/// when written, so it can hold at least its length
+fn write_end(head: &SnakeHead) -> u64 {
+    if head.flags.is_compressed() {
+        head.capacity.max(head.length)
+    } else {
+        head.capacity
+    }
+}
+
+/// [`std::io`] access to a snake. reads stop at the length and writes
+/// stop at the capacity, moving the length forward. the length is set
+/// with [`SnakeDb::set_length`] on flush and drop
+pub struct SnakeHandle<'a> {
+    db: &'a mut SnakeDb,
+    head: SnakeHead,
+    pos: u64,
+    /// the length with the writes that are not flushed yet
+    length: u64,
+    dirty: bool,
+}
+
+impl SnakeHandle<'_> {
+    pub fn head(&self) -> &SnakeHead {
+        &self.head
+    }
+}
+
+fn io_err(e: ShahError) -> io::Error {
+    io::Error::other(format!("{e:?}"))
+}
+
+impl Read for SnakeHandle<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.length {
+            return Ok(0);
+        }
+
+        let n = buf.len().min((self.length - self.pos) as usize);
+        let gene = self.head.gene;
+        self.db
+            .read(&gene, &mut self.head, self.pos, &mut buf[..n])
+            .map_err(io_err)?;
+        self.head.length = self.length;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Write for SnakeHandle<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let end = write_end(&self.head);
+        if self.pos >= end {
+            return Ok(0);
+        }
+
+        let n = buf.len().min((end - self.pos) as usize);
+        let gene = self.head.gene;
+        self.db
+            .write(&gene, &mut self.head, self.pos, &buf[..n])
+            .map_err(io_err)?;
+        self.pos += n as u64;
+        self.length = self.length.max(self.pos);
+        self.head.length = self.length;
+        self.dirty = true;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
+
+        let gene = self.head.gene;
+        self.db
+            .set_length(&gene, &mut self.head, self.length)
+            .map_err(io_err)?;
+        self.length = data_end(&self.head);
+        self.dirty = false;
+        Ok(())
+    }
+}
+
+impl Drop for SnakeHandle<'_> {
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            log::error!("snake handle flush failed: {e:?}");
+        }
+    }
+}
+
+impl Seek for SnakeHandle<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, off) = match pos {
+            SeekFrom::Start(off) => (0, off as i64),
+            SeekFrom::End(off) => (self.length, off),
+            SeekFrom::Current(off) => (self.pos, off),
+        };
+        let Some(pos) = base.checked_add_signed(off) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "seek before the start of the snake",
+            ));
+        };
+        self.pos = pos;
+        Ok(pos)
+    }
+}
+
+type C<Ok, E> = Result<Ok, ClientError<E>>;
+type ReadFn<E> = fn(&Taker, &Gene, &u64) -> C<(SnakeHead, [u8; BLOCK_SIZE]), E>;
+type WriteFn<E> =
+    fn(&Taker, &Gene, &u64, &[u8; BLOCK_SIZE], &u64) -> C<SnakeHead, E>;
+/// client side reads and writes of a snake, one request per block
+pub struct SnakeClient<E: IsNotFound + From<u16> + Copy> {
+    pub head: fn(&Taker, &Gene) -> C<SnakeHead, E>,
+    /// a block of data at the offset
+    pub read: ReadFn<E>,
+    /// write the first `len` bytes of the block at the offset
+    pub write: WriteFn<E>,
+    pub set_length: fn(&Taker, &Gene, &u64) -> C<SnakeHead, E>,
+}
+
+impl<E: IsNotFound + From<u16> + Copy> SnakeClient<E> {
+    pub fn read(&self, taker: &Taker, gene: &Gene) -> C<Vec<u8>, E> {
+        self.read_range(taker, gene, 0, usize::MAX)
+    }
+
+    /// read at most `len` bytes starting at `offset`
+    pub fn read_range(
+        &self, taker: &Taker, gene: &Gene, offset: u64, len: usize,
+    ) -> C<Vec<u8>, E> {
+        gene.validate()?;
+        let head = (self.head)(taker, gene)?;
+
+        let end = offset.saturating_add(len as u64).min(data_end(&head));
+        if offset >= end {
+            return Ok(Vec::new());
+        }
+
+        let mut data = Vec::with_capacity((end - offset) as usize);
+        let mut pos = offset;
+        while pos < end {
+            let (_, buf) = (self.read)(taker, gene, &pos)?;
+            let n = ((end - pos) as usize).min(BLOCK_SIZE);
+            data.extend_from_slice(&buf[..n]);
+            pos += n as u64;
+        }
+
+        Ok(data)
+    }
+
+    /// write the data at `offset`. the data past the capacity is dropped
+    /// and the length is moved forward when the write ends after it
+    pub fn write_at(
+        &self, taker: &Taker, gene: &Gene, offset: u64, data: &[u8],
+    ) -> C<SnakeHead, E> {
+        let (head, end) = self.write_blocks(taker, gene, offset, data)?;
+        (self.set_length)(taker, gene, &end.max(head.length))
+    }
+
+    /// replace the data of the snake, the length is set to its size
+    pub fn write(
+        &self, taker: &Taker, gene: &Gene, data: &[u8],
+    ) -> C<SnakeHead, E> {
+        let (_, end) = self.write_blocks(taker, gene, 0, data)?;
+        (self.set_length)(taker, gene, &end)
+    }
+
+    /// write the blocks without setting the length. returns the end
+    fn write_blocks(
+        &self, taker: &Taker, gene: &Gene, offset: u64, data: &[u8],
+    ) -> C<(SnakeHead, u64), E> {
+        gene.validate()?;
+        let mut head = (self.head)(taker, gene)?;
+
+        let end =
+            offset.saturating_add(data.len() as u64).min(write_end(&head));
+        let mut pos = offset;
+        let mut buf = [0u8; BLOCK_SIZE];
+        while pos < end {
+            let n = ((end - pos) as usize).min(BLOCK_SIZE);
+            let at = (pos - offset) as usize;
+            buf[..n].copy_from_slice(&data[at..at + n]);
+            head = (self.write)(taker, gene, &pos, &buf, &(n as u64))?;
+            pos += n as u64;
+        }
+
+        Ok((head, end))
+    }
+}
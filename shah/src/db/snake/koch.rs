@@ -0,0 +1,55 @@
+use super::{SnakeFlags, SnakeHead};
+use crate::config::ShahConfig;
+use crate::db::entity::{
+    EntityFlags, EntityKoch, EntityKochDb, EntityKochFrom,
+};
+use crate::models::Gene;
+use crate::{Entity, ShahError};
+use std::cell::RefMut;
+
+/// snake head of the index revision 0, before refs and stored
+#[derive(crate::ShahSchema)]
+#[crate::model]
+#[derive(Debug, Entity)]
+pub struct SnakeHeadV0 {
+    pub gene: Gene,
+    pub capacity: u64,
+    pub position: u64,
+    pub length: u64,
+    growth: u64,
+    entity_flags: EntityFlags,
+    pub flags: SnakeFlags,
+    _pad: [u8; 6],
+}
+
+impl EntityKochFrom<SnakeHeadV0> for SnakeHead {
+    fn entity_koch_from(
+        old: SnakeHeadV0, _: RefMut<()>,
+    ) -> Result<Self, ShahError> {
+        Ok(Self {
+            gene: old.gene,
+            capacity: old.capacity,
+            position: old.position,
+            length: old.length,
+            stored: old.length,
+            growth: old.growth,
+            entity_flags: old.entity_flags,
+            flags: old.flags,
+            _pad: [0; 2],
+            refs: 1,
+        })
+    }
+}
+
+/// the koch of the index from revision 0. the heads are koched by the
+/// task of the index and read from the old index until then
+pub(super) fn index_koch(
+    path: &str,
+) -> Result<Option<EntityKoch<SnakeHead, SnakeHeadV0, ()>>, ShahError> {
+    let index = format!("{path}/index");
+    let data_path = ShahConfig::get().data_dir.join(&index);
+    if !data_path.join("index.0.shah").exists() {
+        return Ok(None);
+    }
+    Ok(EntityKoch::new(EntityKochDb::new(&index, 0), ()))
+}
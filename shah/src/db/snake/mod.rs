@@ -1,16 +1,26 @@
 mod api;
+mod compress;
+mod dedup;
+mod defrag;
 mod free;
+mod handle;
+mod koch;
 
-use super::entity::{EntityDb, EntityInspector};
+pub use handle::{SnakeClient, SnakeHandle};
+pub use koch::SnakeHeadV0;
+
+use super::entity::EntityDb;
 use crate::config::ShahConfig;
 use crate::db::entity::EntityFlags;
 use crate::models::{
-    Binary, DbHead, Gene, Performed, ShahMagic, ShahMagicDb, Task, TaskList,
-    Worker,
+    Binary, DbHead, Gene, GeneId, Performed, ShahMagic, ShahMagicDb, Task,
+    TaskList, Worker,
 };
-use crate::{AsStatic, BLOCK_SIZE, Entity, utils};
+use crate::{BLOCK_SIZE, Entity, utils};
 use crate::{NotFound, ShahError, SystemError};
-use std::os::unix::fs::FileExt;
+use dedup::{SnakeDedupDb, SnakeHashAbc};
+use free::SnakeFreeList;
+use std::os::unix::fs::{FileExt, MetadataExt};
 use std::{
     fs::File,
     io::{Seek, SeekFrom},
@@ -18,7 +28,6 @@ use std::{
 
 /// TOLERABLE CAPACITY DIFFERENCE
 const TCD: u64 = 255;
-const FREE_LIST_SIZE: usize = BLOCK_SIZE;
 const SNAKE_MAGIC: ShahMagic = ShahMagic::new_const(ShahMagicDb::Snake as u16);
 const SNAKE_VERSION: u16 = 1;
 
@@ -29,10 +38,33 @@ pub struct SnakeFree {
     capacity: u64,
 }
 
+impl From<&SnakeHead> for SnakeFree {
+    fn from(head: &SnakeHead) -> Self {
+        Self {
+            gene: head.gene,
+            position: head.position,
+            capacity: head.capacity,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SnakeStats {
+    /// size of the data file
+    pub size: u64,
+    /// bytes the data file takes on disk, less than `size` with holes
+    pub disk: u64,
+    /// number of regions in the free list
+    pub free: u64,
+    pub free_bytes: u64,
+}
+
 #[cfg_attr(feature = "serde", shah::flags(inner = u8, serde = true))]
 #[cfg_attr(not(feature = "serde"), shah::flags(inner = u8, serde = false))]
 pub struct SnakeFlags {
-    is_free: bool,
+    pub is_free: bool,
+    /// the content is stored compressed, see [`SnakeDb::set_compress`]
+    pub is_compressed: bool,
 }
 
 #[derive(crate::ShahSchema)]
@@ -42,25 +74,55 @@ pub struct SnakeHead {
     pub gene: Gene,
     pub capacity: u64,
     pub position: u64,
+    /// logical size of the content
     pub length: u64,
+    /// bytes of the content in the file, less than length when compressed
+    pub stored: u64,
     growth: u64,
     entity_flags: EntityFlags,
     pub flags: SnakeFlags,
-    _pad: [u8; 6],
+    _pad: [u8; 2],
+    /// number of owners of a snake shared by alloc_dedup
+    pub refs: u32,
 }
 
-type SnakeIndexDb = EntityDb<SnakeHead, SnakeHead, (), &'static mut SnakeDb>;
+impl SnakeHead {
+    /// bytes of the region in the file. a compressed content keeps its
+    /// capacity but only takes its stored bytes
+    fn region(&self) -> u64 {
+        match self.flags.is_compressed() {
+            true => self.stored,
+            false => self.capacity,
+        }
+    }
+}
+
+const SNAKE_INDEX_REVISION: u16 = 1;
+
+type SnakeIndexDb = EntityDb<SnakeHead, SnakeHeadV0>;
 
 #[derive(Debug)]
 pub struct SnakeDb {
     file: File,
     pub live: u64,
-    pub free: u64,
-    pub free_list: Box<[Option<SnakeFree>; FREE_LIST_SIZE]>,
+    free_list: SnakeFreeList,
+    /// next id of the index to scan for free heads of a new free list.
+    /// 0 is done or not needed
+    free_scan: GeneId,
     pub index: SnakeIndexDb,
+    /// move snakes over the free space and cut the tail. off by default
+    defrag: bool,
+    defrag_cursor: GeneId,
+    /// min bytes of whole blocks in a freed region to punch a hole. 0 is off
+    punch: u64,
+    /// content hash to gene index of alloc_dedup. off by default
+    dedup: Option<SnakeDedupDb>,
+    /// compress the contents on set_length. off by default
+    compress: bool,
+    path: String,
     name: String,
     ls: String,
-    tasks: TaskList<1, Task<Self>>,
+    tasks: TaskList<3, Task<Self>>,
 }
 
 impl SnakeDb {
@@ -83,25 +145,33 @@ impl SnakeDb {
             .truncate(false)
             .open(data_path.join("data.snake.shah"))?;
 
+        let (free_list, fresh) = SnakeFreeList::new(&data_path)?;
+        let koch = koch::index_koch(path)?;
         let mut db = Self {
             live: 0,
-            free: 0,
-            free_list: Box::new([None; BLOCK_SIZE]),
+            free_list,
+            free_scan: GeneId(fresh as u64),
             file,
-            index: SnakeIndexDb::new(&format!("{path}/index"), 0)?,
+            index: SnakeIndexDb::new(
+                &format!("{path}/index"),
+                SNAKE_INDEX_REVISION,
+            )?,
             ls: format!("<Snake {path} />"),
             name: name.to_string(),
-            tasks: TaskList::new([Self::work_index]),
+            defrag: false,
+            defrag_cursor: GeneId(1),
+            punch: 0,
+            dedup: None,
+            compress: false,
+            path: path.to_string(),
+            tasks: TaskList::new([
+                Self::work_index,
+                Self::work_free_scan,
+                Self::work_defrag,
+            ]),
         };
 
-        let dbs = db.as_static();
-        let ei = EntityInspector::new(dbs, |mut db, head: &SnakeHead| {
-            if head.flags.is_free() {
-                db.add_free(*head)?;
-            }
-            Ok(())
-        });
-        db.index.set_inspector(ei);
+        db.index.set_koch(koch)?;
 
         db.init()?;
 
@@ -126,6 +196,44 @@ impl SnakeDb {
         Ok(())
     }
 
+    pub fn set_defrag(&mut self, defrag: bool) {
+        self.defrag = defrag;
+    }
+
+    /// punch holes in freed regions of at least `min` bytes. the space
+    /// is given back to the filesystem and allocated again on write
+    pub fn set_punch_hole(&mut self, min: u64) {
+        self.punch = min;
+    }
+
+    /// keep an index of the content hashes. snakes with the same content
+    /// are shared by alloc_dedup and set_length
+    pub fn set_dedup(&mut self, dedup: bool) -> Result<(), ShahError> {
+        self.dedup = None;
+        if dedup {
+            let name = format!("{}/dedup", self.path);
+            self.dedup = Some(SnakeDedupDb::new(&name, SnakeHashAbc)?);
+        }
+        Ok(())
+    }
+
+    /// store the contents compressed when it makes them smaller. it is
+    /// done by set_length and reads decompress only the blocks they need.
+    /// writing to a compressed snake stores it uncompressed again until
+    /// the next set_length
+    pub fn set_compress(&mut self, compress: bool) {
+        self.compress = compress;
+    }
+
+    pub fn stats(&mut self) -> Result<SnakeStats, ShahError> {
+        Ok(SnakeStats {
+            size: self.file_size()?,
+            disk: self.file.metadata()?.blocks() * 512,
+            free: self.free_list.len(),
+            free_bytes: self.free_list.bytes(),
+        })
+    }
+
     fn work_index(&mut self) -> Result<Performed, ShahError> {
         self.index.work()
     }
@@ -134,6 +242,19 @@ impl SnakeDb {
         self.file.seek(SeekFrom::End(0))
     }
 
+    /// copy `len` bytes of data from `src` to `dst`
+    fn copy(&mut self, src: u64, dst: u64, len: u64) -> Result<(), ShahError> {
+        let mut buf = [0u8; BLOCK_SIZE];
+        let mut done = 0u64;
+        while done < len {
+            let n = (len - done).min(BLOCK_SIZE as u64) as usize;
+            self.file.read_exact_at(&mut buf[..n], src + done)?;
+            self.file.write_all_at(&buf[..n], dst + done)?;
+            done += n as u64;
+        }
+        Ok(())
+    }
+
     fn check_offset(
         &mut self, gene: &Gene, head: &mut SnakeHead, offset: u64,
         buflen: usize,
@@ -144,16 +265,21 @@ impl SnakeDb {
         }
         assert!(head.position >= SnakeHead::N);
         assert_ne!(head.capacity, 0);
-        if offset >= head.capacity {
+        // a compressed content may be longer than its region
+        let end = if head.flags.is_compressed() {
+            head.capacity.max(head.length)
+        } else {
+            head.capacity
+        };
+        if offset >= end {
             log::error!(
-                "{} check_offset: bad offset: {offset} >= {}",
+                "{} check_offset: bad offset: {offset} >= {end}",
                 self.ls,
-                head.capacity
             );
             return Err(SystemError::BadOffset)?;
         }
-        let len = if offset + (buflen as u64) > head.capacity {
-            (head.capacity - offset) as usize
+        let len = if offset + (buflen as u64) > end {
+            (end - offset) as usize
         } else {
             buflen
         };
@@ -162,8 +288,8 @@ impl SnakeDb {
     }
 }
 
-impl Worker<1> for SnakeDb {
-    fn tasks(&mut self) -> &mut TaskList<1, Task<Self>> {
+impl Worker<3> for SnakeDb {
+    fn tasks(&mut self) -> &mut TaskList<3, Task<Self>> {
         &mut self.tasks
     }
 }
@@ -0,0 +1,119 @@
+use std::collections::VecDeque;
+
+use crate::db::entity::{EntityDb, EntityFlags};
+use crate::models::{Gene, GeneId, Performed, Worker};
+use crate::{OptNotFound, PAGE_SIZE, ShahError};
+
+/// a deleted parent whose children are still being deleted.
+/// `next` is the persisted progress, the first child not yet deleted
+#[derive(crate::ShahSchema)]
+#[crate::model]
+#[derive(Debug, crate::Entity)]
+pub struct Sweep {
+    pub gene: Gene,
+    pub parent: Gene,
+    pub next: Gene,
+    /// number of children deleted so far
+    pub done: u64,
+    growth: u64,
+    entity_flags: EntityFlags,
+    _pad: [u8; 7],
+}
+
+/// background cascading delete of buckles and origins.
+/// the sweeps are kept in their own entity db so a restart resumes them
+#[derive(Debug)]
+pub(crate) struct Sweeper {
+    db: EntityDb<Sweep>,
+    pending: VecDeque<Gene>,
+    ls: String,
+}
+
+impl Sweeper {
+    pub(crate) fn new(path: &str) -> Result<Self, ShahError> {
+        let mut db = EntityDb::<Sweep>::new(&format!("{path}/sweep"), 1)?;
+
+        let mut pending = VecDeque::new();
+        let mut page = [Sweep::default(); PAGE_SIZE];
+        let mut id = GeneId(1);
+        while let Some(count) = db.list(id, &mut page).onf()? {
+            for sweep in page[..count].iter() {
+                if sweep.gene.id != 0 && sweep.entity_flags.is_alive() {
+                    pending.push_back(sweep.gene);
+                }
+            }
+            if count < PAGE_SIZE {
+                break;
+            }
+            id += PAGE_SIZE as u64;
+        }
+
+        let ls = format!("<Sweeper {path} />");
+        if !pending.is_empty() {
+            log::info!("{ls} resuming {} sweeps", pending.len());
+        }
+
+        Ok(Self { db, pending, ls })
+    }
+
+    pub(crate) fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub(crate) fn add(
+        &mut self, parent: &Gene, first: &Gene,
+    ) -> Result<(), ShahError> {
+        let mut sweep =
+            Sweep { parent: *parent, next: *first, ..Default::default() };
+        self.db.add(&mut sweep)?;
+        self.pending.push_back(sweep.gene);
+        Ok(())
+    }
+
+    /// the sweep to work on next
+    pub(crate) fn current(&mut self) -> Result<Option<Sweep>, ShahError> {
+        let mut sweep = Sweep::default();
+        while let Some(gene) = self.pending.front() {
+            if self.db.get(gene, &mut sweep).onf()?.is_some() {
+                return Ok(Some(sweep));
+            }
+            self.pending.pop_front();
+        }
+        Ok(None)
+    }
+
+    /// every sweep that is not done yet
+    pub(crate) fn sweeps(&mut self) -> Result<Vec<Sweep>, ShahError> {
+        let mut out = Vec::with_capacity(self.pending.len());
+        let mut sweep = Sweep::default();
+        for gene in self.pending.iter() {
+            if self.db.get(gene, &mut sweep).onf()?.is_some() {
+                out.push(sweep);
+            }
+        }
+        Ok(out)
+    }
+
+    /// persist the progress. the sweep is removed once `next` is none
+    pub(crate) fn update(
+        &mut self, sweep: &mut Sweep,
+    ) -> Result<(), ShahError> {
+        if sweep.next.is_some() {
+            return self.db.set(sweep);
+        }
+
+        log::info!(
+            "{} swept {} children of {:?}",
+            self.ls,
+            sweep.done,
+            sweep.parent
+        );
+        self.pending.retain(|g| *g != sweep.gene);
+        let gene = sweep.gene;
+        self.db.del(&gene, sweep)
+    }
+
+    pub(crate) fn work(&mut self) -> Result<Performed, ShahError> {
+        self.db.work()
+    }
+}
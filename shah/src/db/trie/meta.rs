@@ -2,7 +2,7 @@ use super::TrieAbc;
 use crate::models::{DbHead, ShahMagic, ShahMagicDb};
 use crate::{AsUtf8Str, DbError, ShahError};
 
-pub const TRIE_VERSION: u16 = 1;
+pub const TRIE_VERSION: u16 = 2;
 pub const TRIE_MAGIC: ShahMagic =
     ShahMagic::new_const(ShahMagicDb::Trie as u16);
 
@@ -12,6 +12,8 @@ pub struct TrieMeta {
     pub db: DbHead,
     pub abc_len: u64,
     pub abc: [u8; 4096],
+    /// first node of the free list
+    pub free: u64,
 }
 
 impl TrieMeta {
@@ -21,6 +23,7 @@ impl TrieMeta {
         self.abc_len = chars.len() as u64;
         self.abc = [0; 4096];
         self.abc[..chars.len()].clone_from_slice(chars.as_bytes());
+        self.free = 0;
     }
 
     pub fn check<Abc: TrieAbc>(&self, ls: &str) -> Result<(), ShahError> {
@@ -1,4 +1,6 @@
 mod meta;
+mod prefix;
+mod upgrade;
 
 use crate::config::ShahConfig;
 use crate::{NotFound, ShahError, ShahModel, utils};
@@ -7,10 +9,13 @@ use std::{
     fmt::Debug,
     io::{ErrorKind, Seek, SeekFrom},
     marker::PhantomData,
+    mem::offset_of,
     os::unix::fs::FileExt,
+    path::Path,
 };
 
 pub use meta::*;
+pub use prefix::TrieCursor;
 
 type Pos = u64;
 
@@ -24,6 +29,8 @@ pub trait TrieAbc {
 #[derive(Debug)]
 struct Node<const ABC_LEN: usize, Val: ShahModel> {
     value: Val,
+    /// 1 when the value was set. a free node keeps the next free in child 0
+    has_value: u64,
     child: [Pos; ABC_LEN],
 }
 
@@ -36,6 +43,8 @@ pub struct Trie<
 > {
     abc: Abc,
     file: std::fs::File,
+    /// first node of the free list. 0 when it is empty
+    free: Pos,
     name: String,
     ls: String,
     _val: PhantomData<Val>,
@@ -70,6 +79,7 @@ impl<const ABC_LEN: usize, Abc: TrieAbc, Val: ShahModel>
 
         let mut db = Self {
             file,
+            free: 0,
             abc,
             name: name.to_string(),
             ls: format!("<Trie {name} />"),
@@ -77,12 +87,12 @@ impl<const ABC_LEN: usize, Abc: TrieAbc, Val: ShahModel>
             // _abc_item: PhantomData,
         };
 
-        db.init()?;
+        db.init(&data_path)?;
 
         Ok(db)
     }
 
-    fn init(&mut self) -> Result<(), ShahError> {
+    fn init(&mut self, path: &Path) -> Result<(), ShahError> {
         let mut meta = TrieMeta::default();
         if let Err(e) = self.read_at(&mut meta, 0) {
             e.not_found_ok()?;
@@ -90,7 +100,12 @@ impl<const ABC_LEN: usize, Abc: TrieAbc, Val: ShahModel>
             meta.init::<Abc>(&self.name);
             self.file.write_all_at(meta.as_binary(), 0)?;
         } else {
+            if meta.db.db_version == 1 {
+                self.upgrade_v1(path)?;
+                self.read_at(&mut meta, 0)?;
+            }
             meta.check::<Abc>(&self.ls)?;
+            self.free = meta.free;
         }
 
         let nn = Node::<ABC_LEN, Val>::N;
@@ -166,6 +181,9 @@ impl<const ABC_LEN: usize, Abc: TrieAbc, Val: ShahModel>
             self.read_at(&mut node, pos)?;
         }
 
+        if node.has_value == 0 {
+            return Err(NotFound::NoTrieValue)?;
+        }
         Ok(node.value)
 
         // // self.file.seek(SeekFrom::Start(pos))?;
@@ -196,13 +214,41 @@ impl<const ABC_LEN: usize, Abc: TrieAbc, Val: ShahModel>
         // unreachable!()
     }
 
+    /// a position for a new node from the free list or the end of the file
+    fn new_node(&mut self) -> Result<Pos, ShahError> {
+        if self.free == 0 {
+            return Ok(self.file.seek(SeekFrom::End(0))?);
+        }
+
+        let pos = self.free;
+        let mut node = Node::<ABC_LEN, Val>::default();
+        self.read_at(&mut node, pos)?;
+        self.free_set(node.child[0])?;
+        Ok(pos)
+    }
+
+    fn free_node(&mut self, pos: Pos) -> Result<(), ShahError> {
+        let mut node = Node::<ABC_LEN, Val>::default();
+        node.child[0] = self.free;
+        self.write_at(&node, pos)?;
+        self.free_set(pos)
+    }
+
+    fn free_set(&mut self, pos: Pos) -> Result<(), ShahError> {
+        self.free = pos;
+        let offset = offset_of!(TrieMeta, free) as u64;
+        self.file.write_all_at(&pos.to_le_bytes(), offset)?;
+        Ok(())
+    }
+
     fn add(&mut self, tree: &[usize], value: Val) -> Result<Pos, ShahError> {
-        let mut child_pos = self.file.seek(SeekFrom::End(0))?;
-        let mut node = Node::<ABC_LEN, Val> { value, ..Default::default() };
+        let mut child_pos = self.new_node()?;
+        let mut node =
+            Node::<ABC_LEN, Val> { value, has_value: 1, ..Default::default() };
         self.write_at(&node, child_pos)?;
 
         for x in tree.iter().rev() {
-            let curr_pos = self.file.seek(SeekFrom::End(0))?;
+            let curr_pos = self.new_node()?;
             node.zeroed();
             node.child[*x] = child_pos;
             self.write_at(&node, curr_pos)?;
@@ -245,8 +291,51 @@ impl<const ABC_LEN: usize, Abc: TrieAbc, Val: ShahModel>
             pos = cpos;
         }
 
-        let old_value = node.value;
+        let old_value = (node.has_value != 0).then_some(node.value);
         node.value = val;
+        node.has_value = 1;
+        self.write_at(&node, pos)?;
+        Ok(old_value)
+    }
+
+    /// clear the value of the key. the nodes left with no value and no
+    /// children are freed and reused by set
+    pub fn del(&mut self, key: &TrieKey) -> Result<Option<Val>, ShahError> {
+        let mut node = Node::<ABC_LEN, Val>::default();
+        if self.read_at(&mut node, TrieMeta::N).onf()?.is_none() {
+            return Ok(None);
+        }
+
+        // parents of the node and its index in them
+        let mut path = Vec::with_capacity(key.tree.len() + 1);
+        let mut pos = TrieMeta::N;
+        for x in std::iter::once(&key.root).chain(key.tree.iter()) {
+            let cpos = node.child[*x];
+            if cpos == 0 || self.read_at(&mut node, cpos).onf()?.is_none() {
+                return Ok(None);
+            }
+            path.push((pos, *x));
+            pos = cpos;
+        }
+
+        if node.has_value == 0 {
+            return Ok(None);
+        }
+        let old_value = node.value;
+        node.value = Val::default();
+        node.has_value = 0;
+
+        while node.child.iter().all(|c| *c == 0) {
+            let Some((parent, x)) = path.pop() else { break };
+            self.free_node(pos)?;
+            self.read_at(&mut node, parent)?;
+            node.child[x] = 0;
+            pos = parent;
+            if node.has_value != 0 {
+                break;
+            }
+        }
+
         self.write_at(&node, pos)?;
         Ok(Some(old_value))
     }
@@ -0,0 +1,112 @@
+use super::{Node, Pos, Trie, TrieAbc, TrieKey, TrieMeta};
+use crate::models::Binary;
+use crate::{OptNotFound, ShahError, ShahModel};
+
+/// where a prefix search stopped. the default starts at the first key
+#[derive(Debug, Default, Clone)]
+pub struct TrieCursor {
+    /// abc indexes of the last key that was returned
+    pub last: Vec<usize>,
+    pub done: bool,
+}
+
+impl<const ABC_LEN: usize, Abc: TrieAbc, Val: ShahModel>
+    Trie<ABC_LEN, Abc, Val>
+{
+    /// up to `limit` keys that start with `prefix` and their values, sorted
+    /// by their characters and not by the abc. call again with the same
+    /// cursor for the rest
+    pub fn prefix(
+        &mut self, prefix: &TrieKey, limit: usize, cursor: &mut TrieCursor,
+    ) -> Result<Vec<(String, Val)>, ShahError> {
+        let mut out = Vec::new();
+        if cursor.done {
+            return Ok(out);
+        }
+
+        let mut path = Vec::with_capacity(prefix.tree.len() + 1);
+        path.push(prefix.root);
+        path.extend_from_slice(&prefix.tree);
+
+        let mut node = Node::<ABC_LEN, Val>::default();
+        let mut pos = TrieMeta::N;
+        for x in path.iter() {
+            if self.read_at(&mut node, pos).onf()?.is_none()
+                || node.child[*x] == 0
+            {
+                cursor.done = true;
+                return Ok(out);
+            }
+            pos = node.child[*x];
+        }
+        self.read_at(&mut node, pos)?;
+
+        let abc: Vec<char> = Abc::ABC.chars().collect();
+        let key = |path: &[usize]| path.iter().map(|x| abc[*x]).collect();
+        // the abc indexes sorted by their character and the place of each
+        let mut order: Vec<usize> = (0..ABC_LEN).collect();
+        order.sort_by_key(|x| abc[*x]);
+        let mut rank = vec![0usize; ABC_LEN];
+        for (r, x) in order.iter().enumerate() {
+            rank[*x] = r;
+        }
+
+        // the nodes from the prefix down and the rank of the next child
+        let mut stack: Vec<(Node<ABC_LEN, Val>, usize)> = Vec::new();
+        let resume = cursor.last.len() >= path.len()
+            && cursor.last[..path.len()] == path[..];
+        if resume {
+            for x in cursor.last[path.len()..].iter() {
+                let cpos = node.child[*x];
+                let mut child = Node::default();
+                if cpos == 0 || self.read_at(&mut child, cpos).onf()?.is_none()
+                {
+                    break;
+                }
+                stack.push((node, rank[*x] + 1));
+                path.push(*x);
+                node = child;
+            }
+            if path.len() == cursor.last.len() {
+                stack.push((node, 0));
+            } else {
+                // the last key is gone, go on after it in its parent
+                let x = cursor.last[path.len()];
+                stack.push((node, rank[x] + 1));
+            }
+        } else {
+            if node.has_value != 0 {
+                out.push((key(&path), node.value));
+            }
+            stack.push((node, 0));
+        }
+
+        while let Some((node, next)) = stack.last_mut() {
+            if out.len() >= limit {
+                cursor.last = path;
+                return Ok(out);
+            }
+
+            let Some(r) = (*next..ABC_LEN).find(|r| node.child[order[*r]] != 0)
+            else {
+                stack.pop();
+                path.pop();
+                continue;
+            };
+            *next = r + 1;
+            let x = order[r];
+            let cpos: Pos = node.child[x];
+
+            let mut child = Node::<ABC_LEN, Val>::default();
+            self.read_at(&mut child, cpos)?;
+            path.push(x);
+            if child.has_value != 0 {
+                out.push((key(&path), child.value));
+            }
+            stack.push((child, 0));
+        }
+
+        cursor.done = true;
+        Ok(out)
+    }
+}
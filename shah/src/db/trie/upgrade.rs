@@ -0,0 +1,57 @@
+use super::{Node, Pos, TRIE_VERSION, Trie, TrieAbc, TrieMeta};
+use crate::models::Binary;
+use crate::{ShahError, ShahModel};
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+impl<const ABC_LEN: usize, Abc: TrieAbc, Val: ShahModel>
+    Trie<ABC_LEN, Abc, Val>
+{
+    /// version 1 had no free list in the meta and no has_value in the
+    /// nodes, a zero value was no value. nodes keep their order, so the
+    /// file is written again next to the old one and then replaces it
+    pub(super) fn upgrade_v1(&mut self, path: &Path) -> Result<(), ShahError> {
+        let meta_n = TrieMeta::N - 8;
+        let node_n = Val::N + 8 * ABC_LEN as u64;
+        let new_n = Node::<ABC_LEN, Val>::N;
+        let map = |pos: Pos| {
+            if pos == 0 {
+                0
+            } else {
+                TrieMeta::N + (pos - meta_n) / node_n * new_n
+            }
+        };
+
+        let mut meta = TrieMeta::default();
+        self.file
+            .read_exact_at(&mut meta.as_binary_mut()[..meta_n as usize], 0)?;
+        meta.db.db_version = TRIE_VERSION;
+        meta.free = 0;
+
+        let tmp = path.with_extension("shah.upgrade");
+        let file = std::fs::File::create(&tmp)?;
+        file.write_all_at(meta.as_binary(), 0)?;
+
+        let total = (self.file_size()? - meta_n) / node_n;
+        let mut buf = vec![0u8; node_n as usize];
+        let mut node = Node::<ABC_LEN, Val>::default();
+        for idx in 0..total {
+            self.file.read_exact_at(&mut buf, meta_n + idx * node_n)?;
+            let (value, child) = buf.split_at(Val::N as usize);
+            node.value.as_binary_mut().copy_from_slice(value);
+            node.has_value = value.iter().any(|b| *b != 0) as u64;
+            for (c, old) in node.child.iter_mut().zip(child.chunks_exact(8)) {
+                *c = map(u64::from_ne_bytes(old.try_into().unwrap()));
+            }
+            file.write_all_at(node.as_binary(), TrieMeta::N + idx * new_n)?;
+        }
+
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)?;
+        self.file =
+            std::fs::OpenOptions::new().read(true).write(true).open(path)?;
+        log::info!("{} upgraded {total} nodes from version 1", self.ls);
+
+        Ok(())
+    }
+}
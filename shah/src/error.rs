@@ -137,6 +137,8 @@ pub enum DbError {
     BadInit,
     NoDiskSpace,
     NoKoch,
+    /// the encoded schema does not fit in the entity head
+    SchemaTooBig,
 }
 
 #[shah::enum_int(u16)]
@@ -162,6 +164,34 @@ pub enum SystemError {
     PondNoEmptySlotWasFound,
     TrieKeyEmpty,
     BadCoords,
+    /// SHAH_TOKEN_KEY was not set in the env
+    GeneTokenNoKey,
+    /// gene token is malformed or was not sealed with this key
+    GeneTokenInvalid,
+    /// patch field path was not found in the schema
+    BadPatchPath,
+    /// patch field data does not match the size of the field
+    BadPatchLength,
+    /// patching gene, growth or entity_flags is not allowed
+    PatchProtected,
+    /// filter expression could not be parsed or does not fit the schema
+    BadFilter,
+    /// the anchor belt belongs to another buckle
+    BeltBuckleMismatch,
+    /// sorted belts can not be placed or moved by position
+    BeltIsSorted,
+    /// range queries need a belt with an order
+    BeltNotSorted,
+    /// ducks with an order need a pond with min and max
+    PondNotSorted,
+    /// snakes shared by alloc_dedup can not be changed
+    SnakeIsShared,
+    /// compressed snake content could not be decompressed
+    SnakeBadCompression,
+    /// a blob can not be longer than u32::MAX bytes
+    BlobTooBig,
+    /// the origin is being deleted and does not take new ducks
+    OriginDying,
 }
 
 impl From<std::io::Error> for ShahError {
@@ -9,6 +9,8 @@ pub mod error;
 pub mod models;
 pub mod signals;
 
+pub(crate) mod hash;
+pub(crate) mod lz;
 pub(crate) mod utils;
 
 pub use error::*;
@@ -0,0 +1,156 @@
+//! small, dependency free lz77 codec in the style of the lz4 block format.
+//! every sequence is a token, the literals and a back reference
+
+const MIN_MATCH: usize = 4;
+const HASH_BITS: u32 = 12;
+const MAX_OFFSET: usize = u16::MAX as usize;
+
+fn write_len(out: &mut Vec<u8>, mut n: usize) {
+    while n >= 255 {
+        out.push(255);
+        n -= 255;
+    }
+    out.push(n as u8);
+}
+
+fn read_len(src: &[u8], i: &mut usize) -> Option<usize> {
+    let mut n = 0usize;
+    loop {
+        let b = *src.get(*i)?;
+        *i += 1;
+        n += b as usize;
+        if b != 255 {
+            return Some(n);
+        }
+    }
+}
+
+/// token is the literal length and the match length - 4, four bits each.
+/// 15 means more length bytes follow
+fn write_sequence(out: &mut Vec<u8>, lits: &[u8], mat: Option<(u16, usize)>) {
+    let ml = mat.map_or(0, |(_, len)| len - MIN_MATCH);
+    out.push(((lits.len().min(15) << 4) | ml.min(15)) as u8);
+    if lits.len() >= 15 {
+        write_len(out, lits.len() - 15);
+    }
+    out.extend_from_slice(lits);
+
+    let Some((offset, _)) = mat else { return };
+    out.extend_from_slice(&offset.to_le_bytes());
+    if ml >= 15 {
+        write_len(out, ml - 15);
+    }
+}
+
+pub(crate) fn compress(src: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(src.len() / 2 + 16);
+    // position + 1 of the last time a 4 byte sequence was seen
+    let mut table = vec![0usize; 1 << HASH_BITS];
+    let mut anchor = 0;
+    let mut i = 0;
+
+    while i + MIN_MATCH <= src.len() {
+        let seq = u32::from_le_bytes(src[i..i + 4].try_into().unwrap());
+        let h = (seq.wrapping_mul(2654435761) >> (32 - HASH_BITS)) as usize;
+        let cand = table[h];
+        table[h] = i + 1;
+
+        if cand == 0
+            || i - (cand - 1) > MAX_OFFSET
+            || src[cand - 1..cand + 3] != src[i..i + 4]
+        {
+            i += 1;
+            continue;
+        }
+
+        let m = cand - 1;
+        let mut len = MIN_MATCH;
+        while i + len < src.len() && src[m + len] == src[i + len] {
+            len += 1;
+        }
+
+        write_sequence(&mut out, &src[anchor..i], Some(((i - m) as u16, len)));
+        i += len;
+        anchor = i;
+    }
+
+    write_sequence(&mut out, &src[anchor..], None);
+    out
+}
+
+/// none when the data is not valid or does not decompress to `size` bytes
+pub(crate) fn decompress(src: &[u8], size: usize) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(size);
+    let mut i = 0;
+
+    while i < src.len() {
+        let token = src[i];
+        i += 1;
+
+        let mut lits = (token >> 4) as usize;
+        if lits == 15 {
+            lits += read_len(src, &mut i)?;
+        }
+        if out.len() + lits > size {
+            return None;
+        }
+        out.extend_from_slice(src.get(i..i + lits)?);
+        i += lits;
+        if i == src.len() {
+            break;
+        }
+
+        let offset = src.get(i..i + 2)?;
+        let offset = u16::from_le_bytes([offset[0], offset[1]]) as usize;
+        i += 2;
+        let mut ml = (token & 15) as usize;
+        if ml == 15 {
+            ml += read_len(src, &mut i)?;
+        }
+        ml += MIN_MATCH;
+
+        if offset == 0 || offset > out.len() || out.len() + ml > size {
+            return None;
+        }
+        // byte by byte, a match may overlap its own output
+        let start = out.len() - offset;
+        for k in 0..ml {
+            out.push(out[start + k]);
+        }
+    }
+
+    (out.len() == size).then_some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{compress, decompress};
+
+    #[test]
+    fn round_trip() {
+        let text = "the quick brown fox jumps over the lazy dog. ".repeat(40);
+        let mut noise = Vec::new();
+        let mut x = 7u32;
+        for _ in 0..5000 {
+            x = x.wrapping_mul(1103515245).wrapping_add(12345);
+            noise.push((x >> 16) as u8);
+        }
+
+        let cases: [&[u8]; 6] = [
+            b"",
+            b"a",
+            b"abcabcabcabcabc",
+            text.as_bytes(),
+            &noise,
+            &[0; 70000],
+        ];
+        for data in cases {
+            let packed = compress(data);
+            assert_eq!(decompress(&packed, data.len()).as_deref(), Some(data));
+        }
+
+        assert!(compress(text.as_bytes()).len() * 10 < text.len());
+        assert!(decompress(&compress(text.as_bytes()), 10).is_none());
+        assert!(decompress(&[0xf0], 100).is_none());
+    }
+}
@@ -194,6 +194,66 @@ impl Gene {
     }
 }
 
+/// what happens to a gene id after all of its iterations are used
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum GeneRecycle {
+    /// an exhausted id is never used again
+    #[default]
+    Never,
+    /// an exhausted id is kept in quarantine for the given duration.
+    /// after that its iter is reset and it will be reused with a new pepper
+    Rotate(std::time::Duration),
+    /// use `pepper[0]` as the high byte of the iteration.
+    /// new ids get 256 times more iterations, at the cost of one byte
+    /// of the random pepper
+    Widen,
+}
+
+impl GeneRecycle {
+    /// the id of this gene can not be reused (for now)
+    pub fn exhausted(&self, gene: &Gene) -> bool {
+        match self {
+            Self::Widen => gene.exhausted() && gene.pepper[0] == u8::MAX,
+            _ => gene.exhausted(),
+        }
+    }
+
+    pub fn quarantine(&self) -> Option<std::time::Duration> {
+        match self {
+            Self::Rotate(dur) => Some(*dur),
+            _ => None,
+        }
+    }
+
+    /// set the iteration of a gene with a brand new id
+    pub fn first(&self, gene: &mut Gene) {
+        gene.iter = 0;
+        if *self == Self::Widen {
+            gene.pepper[0] = 0;
+        }
+    }
+
+    /// set the iteration of `gene` that is reusing the id of `dead`.
+    /// returns false if the id of `dead` is exhausted
+    pub fn next(&self, dead: &Gene, gene: &mut Gene) -> bool {
+        if !dead.exhausted() {
+            gene.iter = dead.iter + 1;
+            if *self == Self::Widen {
+                gene.pepper[0] = dead.pepper[0];
+            }
+            return true;
+        }
+
+        if *self != Self::Widen || dead.pepper[0] == u8::MAX {
+            return false;
+        }
+
+        gene.iter = 0;
+        gene.pepper[0] = dead.pepper[0] + 1;
+        true
+    }
+}
+
 #[cfg(feature = "serde")]
 impl utoipa::PartialSchema for Gene {
     fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
@@ -265,3 +325,33 @@ impl<'de> serde::Deserialize<'de> for Gene {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Gene, GeneId, GeneRecycle};
+
+    #[test]
+    fn recycle() {
+        let mut gene = Gene::default();
+        let mut dead =
+            Gene { id: GeneId(7), iter: 12, pepper: [3, 4, 5], server: 1 };
+
+        assert!(GeneRecycle::Never.next(&dead, &mut gene));
+        assert_eq!(gene.iter, 13);
+
+        dead.iter = 250;
+        assert!(GeneRecycle::Never.exhausted(&dead));
+        assert!(!GeneRecycle::Never.next(&dead, &mut gene));
+
+        assert!(!GeneRecycle::Widen.exhausted(&dead));
+        assert!(GeneRecycle::Widen.next(&dead, &mut gene));
+        assert_eq!((gene.iter, gene.pepper[0]), (0, 4));
+
+        dead.pepper[0] = u8::MAX;
+        assert!(GeneRecycle::Widen.exhausted(&dead));
+        assert!(!GeneRecycle::Widen.next(&dead, &mut gene));
+
+        GeneRecycle::Widen.first(&mut gene);
+        assert_eq!((gene.iter, gene.pepper[0]), (0, 0));
+    }
+}
@@ -12,6 +12,7 @@ pub mod schema;
 pub mod server;
 pub mod state;
 pub mod task_list;
+pub mod token;
 
 pub use api::*;
 pub use binary::Binary;
@@ -26,3 +27,4 @@ pub use server::*;
 pub use state::*;
 pub use string::ShahString;
 pub use task_list::*;
+pub use token::{GeneToken, GeneTokenKey};
@@ -0,0 +1,527 @@
+//! a small filter language that is evaluated on the raw bytes of a record.
+//!
+//! `cc == 98 && flags.is_banned == false`
+//! `!(name == "admin") || reviews.0 >= 10`
+//!
+//! a comparison is always `field op literal`. a bool field or a one bit
+//! flag can also be used on its own, e.g. `flags.is_banned`
+
+use super::Schema;
+use crate::{ShahError, SystemError};
+use std::cmp::Ordering;
+
+fn err<T>(msg: impl std::fmt::Display) -> Result<T, ShahError> {
+    log::warn!("filter: {msg}");
+    Err(SystemError::BadFilter)?
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Num {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    Bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Num { offset: usize, num: Num },
+    Bits { offset: usize, start: u16, bits: u8 },
+    Str { offset: usize, len: usize },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Int(i128),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn test(self, ord: Option<Ordering>) -> bool {
+        let Some(ord) = ord else { return self == Self::Ne };
+        match self {
+            Self::Eq => ord.is_eq(),
+            Self::Ne => ord.is_ne(),
+            Self::Lt => ord.is_lt(),
+            Self::Le => ord.is_le(),
+            Self::Gt => ord.is_gt(),
+            Self::Ge => ord.is_ge(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Value(Value),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+#[derive(Debug)]
+enum Node {
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Not(Box<Node>),
+    Cmp(Field, Op, Value),
+}
+
+/// a compiled filter expression. see the [module docs](self)
+#[derive(Debug)]
+pub struct Filter {
+    root: Node,
+    size: usize,
+}
+
+impl Field {
+    fn new(schema: &Schema, path: &str) -> Result<Self, ShahError> {
+        if let Some((offset, kind)) = schema.resolve(path) {
+            let num = match kind {
+                Schema::U8 => Num::U8,
+                Schema::U16 => Num::U16,
+                Schema::U32 => Num::U32,
+                Schema::U64 => Num::U64,
+                Schema::I8 => Num::I8,
+                Schema::I16 => Num::I16,
+                Schema::I32 => Num::I32,
+                Schema::I64 => Num::I64,
+                Schema::F32 => Num::F32,
+                Schema::F64 => Num::F64,
+                Schema::Bool => Num::Bool,
+                Schema::Flags { kind, .. } if kind.size() <= 8 => {
+                    let bits = (kind.size() * 8) as u8;
+                    return Ok(Self::Bits { offset, start: 0, bits });
+                }
+                Schema::Array { is_str: true, length, kind }
+                    if **kind == Schema::U8 =>
+                {
+                    return Ok(Self::Str { offset, len: *length as usize });
+                }
+                _ => return err(format!("{path:?} can not be compared")),
+            };
+            return Ok(Self::Num { offset, num });
+        }
+
+        if let Some((parent, name)) = path.rsplit_once('.')
+            && let Some((offset, Schema::Flags { fields, .. })) =
+                schema.resolve(parent)
+            && let Some((_, start, bits)) = fields.iter().find(|f| f.0 == name)
+        {
+            return Ok(Self::Bits { offset, start: *start, bits: *bits });
+        }
+
+        err(format!("unknown field {path:?}"))
+    }
+
+    fn is_bool(&self) -> bool {
+        matches!(
+            self,
+            Self::Num { num: Num::Bool, .. } | Self::Bits { bits: 1, .. }
+        )
+    }
+
+    fn check(&self, value: &Value) -> bool {
+        match self {
+            Self::Str { .. } => matches!(value, Value::Str(_)),
+            _ if self.is_bool() => matches!(value, Value::Bool(_)),
+            _ => matches!(value, Value::Int(_) | Value::Float(_)),
+        }
+    }
+
+    fn cmp(&self, data: &[u8], value: &Value) -> Option<Ordering> {
+        macro_rules! num {
+            ($offset:expr, $ty:ty) => {{
+                const LEN: usize = size_of::<$ty>();
+                let mut buf = [0u8; LEN];
+                buf.copy_from_slice(&data[$offset..$offset + LEN]);
+                <$ty>::from_le_bytes(buf)
+            }};
+        }
+
+        let (int, float) = match *self {
+            Self::Str { offset, len } => {
+                let Value::Str(value) = value else { return None };
+                let raw = &data[offset..offset + len];
+                let end = raw.iter().position(|c| *c == 0).unwrap_or(len);
+                return Some(raw[..end].cmp(value.as_bytes()));
+            }
+            Self::Bits { offset, start, bits } => {
+                let mut v = 0i128;
+                for i in 0..bits as usize {
+                    let b = start as usize + i;
+                    let bit = (data[offset + b / 8] >> (b % 8)) & 1;
+                    v |= (bit as i128) << i;
+                }
+                if bits == 1 {
+                    let Value::Bool(value) = value else { return None };
+                    return Some((v == 1).cmp(value));
+                }
+                (v, None)
+            }
+            Self::Num { offset, num } => match num {
+                Num::Bool => {
+                    let Value::Bool(value) = value else { return None };
+                    return Some((data[offset] != 0).cmp(value));
+                }
+                Num::U8 => (data[offset] as i128, None),
+                Num::I8 => (data[offset] as i8 as i128, None),
+                Num::U16 => (num!(offset, u16) as i128, None),
+                Num::I16 => (num!(offset, i16) as i128, None),
+                Num::U32 => (num!(offset, u32) as i128, None),
+                Num::I32 => (num!(offset, i32) as i128, None),
+                Num::U64 => (num!(offset, u64) as i128, None),
+                Num::I64 => (num!(offset, i64) as i128, None),
+                Num::F32 => (0, Some(num!(offset, f32) as f64)),
+                Num::F64 => (0, Some(num!(offset, f64))),
+            },
+        };
+
+        match (float, value) {
+            (None, Value::Int(v)) => Some(int.cmp(v)),
+            (None, Value::Float(v)) => (int as f64).partial_cmp(v),
+            (Some(f), Value::Int(v)) => f.partial_cmp(&(*v as f64)),
+            (Some(f), Value::Float(v)) => f.partial_cmp(v),
+            _ => None,
+        }
+    }
+}
+
+impl Node {
+    fn test(&self, data: &[u8]) -> bool {
+        match self {
+            Self::And(a, b) => a.test(data) && b.test(data),
+            Self::Or(a, b) => a.test(data) || b.test(data),
+            Self::Not(a) => !a.test(data),
+            Self::Cmp(field, op, value) => op.test(field.cmp(data, value)),
+        }
+    }
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, ShahError> {
+    let src = expr.as_bytes();
+    let mut out = Vec::new();
+    let mut i = 0usize;
+    while i < src.len() {
+        let c = src[i];
+        let next = src.get(i + 1).copied();
+        let (token, len) = match (c, next) {
+            (b' ' | b'\t' | b'\n' | b'\r', _) => {
+                i += 1;
+                continue;
+            }
+            (b'(', _) => (Token::Open, 1),
+            (b')', _) => (Token::Close, 1),
+            (b'&', Some(b'&')) => (Token::And, 2),
+            (b'|', Some(b'|')) => (Token::Or, 2),
+            (b'=', Some(b'=')) => (Token::Op(Op::Eq), 2),
+            (b'!', Some(b'=')) => (Token::Op(Op::Ne), 2),
+            (b'<', Some(b'=')) => (Token::Op(Op::Le), 2),
+            (b'>', Some(b'=')) => (Token::Op(Op::Ge), 2),
+            (b'<', _) => (Token::Op(Op::Lt), 1),
+            (b'>', _) => (Token::Op(Op::Gt), 1),
+            (b'!', _) => (Token::Not, 1),
+            (b'"', _) => {
+                let mut value = String::new();
+                let mut end = None;
+                let mut chars = expr[i + 1..].char_indices();
+                while let Some((x, ch)) = chars.next() {
+                    match ch {
+                        '"' => {
+                            end = Some(x);
+                            break;
+                        }
+                        '\\' => match chars.next() {
+                            Some((_, ch)) => value.push(ch),
+                            None => break,
+                        },
+                        ch => value.push(ch),
+                    }
+                }
+                let Some(end) = end else {
+                    return err(format!("unterminated string at {i}"));
+                };
+                (Token::Value(Value::Str(value)), end + 2)
+            }
+            (b'0'..=b'9', _) | (b'-', Some(b'0'..=b'9')) => {
+                let len = src[i + 1..]
+                    .iter()
+                    .position(|c| !c.is_ascii_alphanumeric() && *c != b'.')
+                    .map_or(src.len() - i, |l| l + 1);
+                let lit = &expr[i..i + len];
+                let value = if let Ok(v) = lit.parse::<i128>() {
+                    Value::Int(v)
+                } else if let Ok(v) = lit.parse::<f64>() {
+                    Value::Float(v)
+                } else {
+                    return err(format!("bad number {lit:?}"));
+                };
+                (Token::Value(value), len)
+            }
+            (b'a'..=b'z' | b'A'..=b'Z' | b'_', _) => {
+                let len = src[i..]
+                    .iter()
+                    .position(|c| {
+                        !c.is_ascii_alphanumeric() && *c != b'_' && *c != b'.'
+                    })
+                    .unwrap_or(src.len() - i);
+                let token = match &expr[i..i + len] {
+                    "true" => Token::Value(Value::Bool(true)),
+                    "false" => Token::Value(Value::Bool(false)),
+                    ident => Token::Ident(ident.to_string()),
+                };
+                (token, len)
+            }
+            _ => return err(format!("unexpected {:?} at {i}", c as char)),
+        };
+        out.push(token);
+        i += len;
+    }
+
+    Ok(out)
+}
+
+/// how deep the tree of an expression can get. every `!`, `(`, `&&` and
+/// `||` is one level, so a long expression can not overflow the stack
+const MAX_DEPTH: usize = 64;
+
+struct Parser<'a> {
+    schema: &'a Schema,
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn deeper(&mut self) -> Result<(), ShahError> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            return err(format!("expression is deeper than {MAX_DEPTH}"));
+        }
+        Ok(())
+    }
+
+    fn or(&mut self) -> Result<Node, ShahError> {
+        let depth = self.depth;
+        let mut node = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            self.deeper()?;
+            node = Node::Or(Box::new(node), Box::new(self.and()?));
+        }
+        self.depth = depth;
+        Ok(node)
+    }
+
+    fn and(&mut self) -> Result<Node, ShahError> {
+        let depth = self.depth;
+        let mut node = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            self.deeper()?;
+            node = Node::And(Box::new(node), Box::new(self.unary()?));
+        }
+        self.depth = depth;
+        Ok(node)
+    }
+
+    fn unary(&mut self) -> Result<Node, ShahError> {
+        let depth = self.depth;
+        let node = match self.next() {
+            Some(Token::Not) => {
+                self.deeper()?;
+                Node::Not(Box::new(self.unary()?))
+            }
+            Some(Token::Open) => {
+                self.deeper()?;
+                let node = self.or()?;
+                if self.next() != Some(Token::Close) {
+                    return err("expected )");
+                }
+                node
+            }
+            Some(Token::Ident(path)) => {
+                let field = Field::new(self.schema, &path)?;
+                let Some(Token::Op(op)) = self.peek().cloned() else {
+                    if field.is_bool() {
+                        self.depth = depth;
+                        return Ok(Node::Cmp(field, Op::Eq, Value::Bool(true)));
+                    }
+                    return err(format!("expected an operator after {path}"));
+                };
+                self.pos += 1;
+
+                let Some(Token::Value(value)) = self.next() else {
+                    return err(format!("expected a value after {path}"));
+                };
+                if !field.check(&value) {
+                    return err(format!(
+                        "{path} can not be compared to {value:?}"
+                    ));
+                }
+                if matches!(value, Value::Bool(_))
+                    && !matches!(op, Op::Eq | Op::Ne)
+                {
+                    return err(format!("{path} only supports == and !="));
+                }
+
+                Node::Cmp(field, op, value)
+            }
+            t => return err(format!("unexpected token: {t:?}")),
+        };
+        self.depth = depth;
+        Ok(node)
+    }
+}
+
+impl Filter {
+    pub fn new(schema: &Schema, expr: &str) -> Result<Self, ShahError> {
+        let tokens = tokenize(expr)?;
+        let mut parser = Parser { schema, tokens, pos: 0, depth: 0 };
+        let root = parser.or()?;
+        if let Some(t) = parser.peek() {
+            return err(format!("unexpected token: {t:?}"));
+        }
+
+        Ok(Self { root, size: schema.size() })
+    }
+
+    /// test the raw bytes of a record
+    pub fn test(&self, data: &[u8]) -> bool {
+        if data.len() < self.size {
+            return false;
+        }
+        self.root.test(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Filter, MAX_DEPTH, Schema};
+    use crate::models::SchemaModel;
+
+    fn schema() -> Schema {
+        Schema::Model(SchemaModel {
+            name: "user".to_string(),
+            size: 16,
+            fields: vec![
+                ("cc".to_string(), Schema::U16),
+                ("temp".to_string(), Schema::I16),
+                (
+                    "flags".to_string(),
+                    Schema::Flags {
+                        kind: Box::new(Schema::U8),
+                        fields: vec![
+                            ("is_banned".to_string(), 0, 1),
+                            ("level".to_string(), 1, 3),
+                        ],
+                    },
+                ),
+                ("ok".to_string(), Schema::Bool),
+                (
+                    "name".to_string(),
+                    Schema::Array {
+                        is_str: true,
+                        length: 8,
+                        kind: Box::new(Schema::U8),
+                    },
+                ),
+                ("score".to_string(), Schema::F32),
+            ],
+        })
+    }
+
+    fn record(cc: u16, temp: i16, flags: u8, name: &str) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&cc.to_le_bytes());
+        out.extend_from_slice(&temp.to_le_bytes());
+        out.push(flags);
+        out.push(1);
+        let mut n = [0u8; 8];
+        n[..name.len()].copy_from_slice(name.as_bytes());
+        out.extend_from_slice(&n);
+        out.extend_from_slice(&2.5f32.to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn filter() {
+        let s = schema();
+        let a = record(98, -4, 0b1011, "admin");
+        let b = record(1, 20, 0, "bob");
+
+        let t = |expr: &str, data: &[u8]| {
+            Filter::new(&s, expr).expect(expr).test(data)
+        };
+
+        assert!(t("cc == 98", &a));
+        assert!(!t("cc == 98", &b));
+        assert!(t("cc == 98 && flags.is_banned == true", &a));
+        assert!(t("cc == 1 && !flags.is_banned", &b));
+        assert!(t("flags.level == 5", &a));
+        assert!(t("flags == 11", &a));
+        assert!(t("temp < 0 && temp >= -4", &a));
+        assert!(t("name == \"admin\"", &a));
+        assert!(t("name < \"b\"", &a));
+        assert!(t("!(name == \"admin\") || cc > 100", &b));
+        assert!(t("ok && score > 2 && score <= 2.5", &a));
+        assert!(t("cc == 5 || cc == 1 && temp == 20", &b));
+
+        for bad in [
+            "",
+            "cc",
+            "cc == ",
+            "cc == \"x\"",
+            "name == 3",
+            "flags.is_banned == 1",
+            "nope == 1",
+            "(cc == 1",
+            "cc == 1)",
+            "cc = 1",
+            "name == \"x",
+        ] {
+            assert!(Filter::new(&s, bad).is_err(), "{bad:?}");
+        }
+
+        let deep = "!".repeat(100_000) + "ok";
+        assert!(Filter::new(&s, &deep).is_err());
+        let deep = "(".repeat(100_000) + "ok" + &")".repeat(100_000);
+        assert!(Filter::new(&s, &deep).is_err());
+        let long = vec!["cc == 98"; 100_000].join(" && ");
+        assert!(Filter::new(&s, &long).is_err());
+        let nested = "!".repeat(MAX_DEPTH) + "ok";
+        assert!(t(&nested, &a));
+    }
+}
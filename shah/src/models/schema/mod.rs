@@ -2,11 +2,19 @@ use crate::error::{DbError, ShahError};
 
 use super::{Binary, Gene};
 
+mod filter;
+
+pub use filter::Filter;
+
 #[derive(Debug, crate::EnumCode)]
 #[enum_code(u8)]
 pub enum Schema {
     Model(SchemaModel),
-    Array { is_str: bool, length: u64, kind: Box<Schema> },
+    Array {
+        is_str: bool,
+        length: u64,
+        kind: Box<Schema>,
+    },
     Tuple(Vec<Schema>),
     U8,
     U16,
@@ -20,10 +28,26 @@ pub enum Schema {
     F64,
     Bool,
     Gene, // 14
+    /// bit flags over an integer or a byte array.
+    /// fields are `(name, bit offset, bits)`
+    Flags {
+        kind: Box<Schema>,
+        fields: Vec<(String, u16, u8)>,
+    },
 }
 
 impl PartialEq for Schema {
     fn eq(&self, other: &Self) -> bool {
+        // flags are stored the same as their inner type
+        match (self, other) {
+            (Self::Flags { kind: sk, .. }, Self::Flags { kind: ok, .. }) => {
+                return sk == ok;
+            }
+            (Self::Flags { kind, .. }, _) => return **kind == *other,
+            (_, Self::Flags { kind, .. }) => return *self == **kind,
+            _ => {}
+        }
+
         match self {
             Self::Model(sm) => matches!(other, Self::Model(om) if sm == om),
             // Self::Model(sm) => match other {
@@ -57,6 +81,7 @@ impl PartialEq for Schema {
             Self::F64 => matches!(other, Self::F64),
             Self::Bool => matches!(other, Self::Bool),
             Self::Gene => matches!(other, Self::Gene),
+            Self::Flags { .. } => unreachable!(),
         }
     }
 }
@@ -67,7 +92,10 @@ impl Schema {
 
         fn check_schema(out: &mut Vec<u8>, schema: &Schema) {
             match schema {
-                Schema::Model(_) | Schema::Array { .. } | Schema::Tuple(_) => {
+                Schema::Model(_)
+                | Schema::Array { .. }
+                | Schema::Tuple(_)
+                | Schema::Flags { .. } => {
                     out.extend_from_slice(&Schema::encode(schema));
                 }
                 _ => {
@@ -99,6 +127,16 @@ impl Schema {
                     check_schema(&mut out, ty);
                 }
             }
+            Self::Flags { kind, fields } => {
+                check_schema(&mut out, kind);
+                out.extend_from_slice(&(fields.len() as u16).to_le_bytes());
+                for (ident, offset, bits) in fields.iter() {
+                    out.extend_from_slice(ident.as_bytes());
+                    out.push(0);
+                    out.extend_from_slice(&offset.to_le_bytes());
+                    out.push(*bits);
+                }
+            }
             _ => {}
         }
         out
@@ -123,9 +161,45 @@ impl Schema {
                 v.iter().fold(0usize, |total, s| total + s.size())
             }
             Self::Model(m) => m.size as usize,
+            Self::Flags { kind, .. } => kind.size(),
         }
     }
 
+    /// find the offset and the schema of a field by its path.
+    /// segments are separated by `.` and array items are addressed by
+    /// their index. e.g. `sessions.1.info.os`
+    pub fn resolve(&self, path: &str) -> Option<(usize, &Schema)> {
+        let mut offset = 0usize;
+        let mut schema = self;
+        for seg in path.split('.') {
+            match schema {
+                Self::Model(m) => {
+                    let mut field = None;
+                    for (ident, kind) in m.fields.iter() {
+                        if ident == seg {
+                            field = Some(kind);
+                            break;
+                        }
+                        offset += kind.size();
+                    }
+                    schema = field?;
+                }
+                Self::Array { length, kind, .. } => {
+                    let idx = seg.parse::<u64>().ok()?;
+                    if idx >= *length {
+                        return None;
+                    }
+                    offset += idx as usize * kind.size();
+                    schema = kind;
+                }
+                // tuples do not have a defined layout
+                _ => return None,
+            }
+        }
+
+        Some((offset, schema))
+    }
+
     fn from_iter(it: &mut core::slice::Iter<u8>) -> Option<Self> {
         macro_rules! from_iter {
             (str) => {{
@@ -152,7 +226,7 @@ impl Schema {
                 for _ in 0..flen {
                     let ident = from_iter!(str);
                     let kind = match *it.clone().next()? {
-                        0 | 1 => Self::from_iter(it)?,
+                        0..=2 | 15 => Self::from_iter(it)?,
                         c => {
                             it.next();
                             Self::from_enum_code(c)?
@@ -167,7 +241,7 @@ impl Schema {
                 let length = from_iter!(u64);
                 let is_str = *it.next()? == 1;
                 let kind = Box::new(match *it.clone().next()? {
-                    0 | 1 => Self::from_iter(it)?,
+                    0..=2 | 15 => Self::from_iter(it)?,
                     c => {
                         it.next();
                         Self::from_enum_code(c)?
@@ -181,7 +255,7 @@ impl Schema {
                 let mut items = Vec::<Schema>::with_capacity(ilen);
                 for _ in 0..ilen {
                     let kind = match *it.clone().next()? {
-                        0..=2 => Self::from_iter(it)?,
+                        0..=2 | 15 => Self::from_iter(it)?,
                         c => {
                             it.next();
                             Self::from_enum_code(c)?
@@ -192,6 +266,25 @@ impl Schema {
 
                 Some(Schema::Tuple(items))
             }
+            15 => {
+                let kind = Box::new(match *it.clone().next()? {
+                    1 => Self::from_iter(it)?,
+                    c => {
+                        it.next();
+                        Self::from_enum_code(c)?
+                    }
+                });
+                let flen = from_iter!(u16) as usize;
+                let mut fields = Vec::with_capacity(flen);
+                for _ in 0..flen {
+                    let ident = from_iter!(str);
+                    let offset = from_iter!(u16);
+                    let bits = *it.next()?;
+                    fields.push((ident, offset, bits));
+                }
+
+                Some(Schema::Flags { kind, fields })
+            }
             _ => None,
         }
     }
@@ -381,4 +474,91 @@ mod tests {
             }),
         );
     }
+
+    #[test]
+    fn resolve() {
+        let inner = Schema::Model(SchemaModel {
+            name: "inner".to_string(),
+            size: 4,
+            fields: vec![
+                ("a".to_string(), Schema::U16),
+                ("b".to_string(), Schema::U8),
+                ("c".to_string(), Schema::U8),
+            ],
+        });
+        let schema = Schema::Model(SchemaModel {
+            name: "outer".to_string(),
+            size: 24,
+            fields: vec![
+                ("gene".to_string(), Schema::Gene),
+                (
+                    "items".to_string(),
+                    Schema::Array {
+                        is_str: false,
+                        length: 2,
+                        kind: Box::new(inner),
+                    },
+                ),
+            ],
+        });
+
+        let (off, kind) = schema.resolve("gene").unwrap();
+        assert_eq!((off, kind), (0, &Schema::Gene));
+        let (off, kind) = schema.resolve("items.1.b").unwrap();
+        assert_eq!((off, kind), (22, &Schema::U8));
+        let (off, kind) = schema.resolve("items.0").unwrap();
+        assert_eq!((off, kind.size()), (16, 4));
+
+        assert!(schema.resolve("").is_none());
+        assert!(schema.resolve("items.2").is_none());
+        assert!(schema.resolve("items.x").is_none());
+        assert!(schema.resolve("gene.id").is_none());
+        assert!(schema.resolve("nope").is_none());
+    }
+
+    #[test]
+    fn flags() {
+        let flags = Schema::Flags {
+            kind: Box::new(Schema::U8),
+            fields: vec![("is_banned".to_string(), 0, 1)],
+        };
+        let schema = Schema::Model(SchemaModel {
+            name: "user".to_string(),
+            size: 3,
+            fields: vec![
+                ("cc".to_string(), Schema::U16),
+                ("flags".to_string(), flags),
+            ],
+        });
+
+        let decoded = Schema::decode(&schema.encode()).unwrap();
+        let Schema::Model(m) = &decoded else { panic!("not a model") };
+        let Schema::Flags { fields, .. } = &m.fields[1].1 else {
+            panic!("not flags")
+        };
+        assert_eq!(fields[0], ("is_banned".to_string(), 0, 1));
+
+        // older dbs stored flags as their inner type
+        let old = Schema::Model(SchemaModel {
+            name: "user".to_string(),
+            size: 3,
+            fields: vec![
+                ("cc".to_string(), Schema::U16),
+                ("flags".to_string(), Schema::U8),
+            ],
+        });
+        assert_eq!(old, decoded);
+        assert_eq!(decoded, old);
+        assert_ne!(
+            old,
+            Schema::Model(SchemaModel {
+                name: "user".to_string(),
+                size: 3,
+                fields: vec![
+                    ("cc".to_string(), Schema::U16),
+                    ("flags".to_string(), Schema::I8),
+                ],
+            })
+        );
+    }
 }
@@ -0,0 +1,299 @@
+use super::{Binary, Gene, Schema, ShahSchema};
+use crate::config::ShahConfig;
+use crate::error::{ShahError, SystemError};
+use crate::hash::{SHA256_LEN, ct_eq, hmac_sha256, sha256};
+
+const TAG_LEN: usize = 16;
+const RAW_LEN: usize = TAG_LEN + Gene::S;
+/// length of a gene token string. 32 bytes in unpadded base64url
+pub const GENE_TOKEN_LEN: usize = (RAW_LEN * 4).div_ceil(3);
+
+const ABC: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// server secret used to seal and open gene tokens.
+/// the default one is read from the `SHAH_TOKEN_KEY` env var
+#[derive(Clone, PartialEq, Eq)]
+pub struct GeneTokenKey([u8; SHA256_LEN]);
+
+impl std::fmt::Debug for GeneTokenKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("GeneTokenKey(..)")
+    }
+}
+
+impl GeneTokenKey {
+    pub fn new(secret: &[u8]) -> Self {
+        Self(sha256(secret))
+    }
+
+    pub fn get() -> Result<&'static Self, ShahError> {
+        match &ShahConfig::get().token_key {
+            Some(key) => Ok(key),
+            None => {
+                log::error!("SHAH_TOKEN_KEY is not set");
+                Err(SystemError::GeneTokenNoKey)?
+            }
+        }
+    }
+
+    fn tag(&self, plain: &[u8]) -> [u8; TAG_LEN] {
+        let mac = hmac_sha256(&self.0, &[b"shah.gene.tag", plain]);
+        mac[..TAG_LEN].try_into().unwrap()
+    }
+
+    fn pad(&self, tag: &[u8]) -> [u8; Gene::S] {
+        let mac = hmac_sha256(&self.0, &[b"shah.gene.pad", tag]);
+        mac[..Gene::S].try_into().unwrap()
+    }
+
+    /// deterministic authenticated encryption (siv style).
+    /// the tag is a mac of the gene and also the iv of the cipher,
+    /// so the token reveals nothing about the id, iter or server
+    pub fn seal(&self, gene: &Gene) -> String {
+        let plain = gene.as_binary();
+        let mut raw = [0u8; RAW_LEN];
+        let (tag, body) = raw.split_at_mut(TAG_LEN);
+        tag.copy_from_slice(&self.tag(plain));
+        for ((b, p), k) in body.iter_mut().zip(plain).zip(self.pad(tag)) {
+            *b = p ^ k;
+        }
+
+        let mut out = String::with_capacity(GENE_TOKEN_LEN);
+        for chunk in raw.chunks(3) {
+            let mut n = [0u8; 3];
+            n[..chunk.len()].copy_from_slice(chunk);
+            let n = u32::from_be_bytes([0, n[0], n[1], n[2]]);
+            for i in 0..=chunk.len() {
+                out.push(ABC[(n >> (18 - i * 6)) as usize & 63] as char);
+            }
+        }
+        out
+    }
+
+    pub fn open(&self, token: &str) -> Result<Gene, ShahError> {
+        let token = token.as_bytes();
+        if token.len() != GENE_TOKEN_LEN {
+            return Err(SystemError::GeneTokenInvalid)?;
+        }
+
+        let mut raw = [0u8; RAW_LEN];
+        for (chunk, out) in token.chunks(4).zip(raw.chunks_mut(3)) {
+            let mut n = 0u32;
+            for (i, c) in chunk.iter().enumerate() {
+                let Some(v) = ABC.iter().position(|a| a == c) else {
+                    return Err(SystemError::GeneTokenInvalid)?;
+                };
+                n |= (v as u32) << (18 - i * 6);
+            }
+            let n = n.to_be_bytes();
+            // unused trailing bits must be zero, one token per gene
+            if n[1 + out.len()..].iter().any(|b| *b != 0) {
+                return Err(SystemError::GeneTokenInvalid)?;
+            }
+            out.copy_from_slice(&n[1..1 + out.len()]);
+        }
+
+        let (tag, body) = raw.split_at(TAG_LEN);
+        let mut gene = Gene::default();
+        let plain = gene.as_binary_mut();
+        for ((p, b), k) in plain.iter_mut().zip(body).zip(self.pad(tag)) {
+            *p = b ^ k;
+        }
+
+        if !ct_eq(tag, &self.tag(gene.as_binary())) {
+            return Err(SystemError::GeneTokenInvalid)?;
+        }
+
+        Ok(gene)
+    }
+}
+
+impl Gene {
+    /// seal this gene into an opaque url safe token using
+    /// the server key from the config
+    pub fn token(&self) -> Result<String, ShahError> {
+        Ok(GeneTokenKey::get()?.seal(self))
+    }
+
+    pub fn from_token(token: &str) -> Result<Self, ShahError> {
+        GeneTokenKey::get()?.open(token)
+    }
+}
+
+/// a gene that is exposed as a sealed token instead of hex.
+/// it has the exact layout and schema of [`Gene`] so it can replace
+/// a gene field in a model without a new revision
+#[crate::model]
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct GeneToken {
+    pub gene: Gene,
+}
+
+impl ShahSchema for GeneToken {
+    fn shah_schema() -> Schema {
+        Schema::Gene
+    }
+}
+
+impl From<Gene> for GeneToken {
+    fn from(gene: Gene) -> Self {
+        Self { gene }
+    }
+}
+
+impl From<GeneToken> for Gene {
+    fn from(value: GeneToken) -> Self {
+        value.gene
+    }
+}
+
+impl core::ops::Deref for GeneToken {
+    type Target = Gene;
+    fn deref(&self) -> &Self::Target {
+        &self.gene
+    }
+}
+
+impl core::ops::DerefMut for GeneToken {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.gene
+    }
+}
+
+impl std::str::FromStr for GeneToken {
+    type Err = ShahError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Self { gene: Gene::from_token(s)? })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl utoipa::PartialSchema for GeneToken {
+    fn schema() -> utoipa::openapi::RefOr<utoipa::openapi::schema::Schema> {
+        use utoipa::openapi as u;
+        u::ObjectBuilder::new()
+            .schema_type(u::schema::SchemaType::Type(u::Type::String))
+            .title(Some("GeneToken"))
+            .description(Some("sealed url safe token of a gene"))
+            .max_length(Some(GENE_TOKEN_LEN))
+            .min_length(Some(GENE_TOKEN_LEN))
+            .into()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl utoipa::ToSchema for GeneToken {}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for GeneToken {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        as_token::serialize(&self.gene, serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for GeneToken {
+    fn deserialize<D>(de: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        Ok(Self { gene: as_token::deserialize(de)? })
+    }
+}
+
+/// serde helpers for plain gene fields.
+/// `#[serde(with = "shah::models::token::as_token")]`
+#[cfg(feature = "serde")]
+pub mod as_token {
+    use super::{GENE_TOKEN_LEN, Gene};
+
+    pub fn serialize<S>(gene: &Gene, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        if gene.is_none() {
+            return serializer.serialize_none();
+        }
+
+        match gene.token() {
+            Ok(v) => serializer.serialize_str(&v),
+            Err(e) => Err(serde::ser::Error::custom(format!("{e:?}"))),
+        }
+    }
+
+    pub fn deserialize<'de, D>(de: D) -> Result<Gene, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::Deserialize;
+
+        let Some(v) = Option::<String>::deserialize(de)? else {
+            return Ok(Gene::default());
+        };
+
+        if v.len() != GENE_TOKEN_LEN {
+            return Err(serde::de::Error::custom(format!(
+                "gene token invalid length {}, expected {GENE_TOKEN_LEN}",
+                v.len(),
+            )));
+        }
+
+        Gene::from_token(&v)
+            .map_err(|_| serde::de::Error::custom("invalid gene token"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GENE_TOKEN_LEN, GeneTokenKey};
+    use crate::models::{Gene, GeneId};
+
+    #[test]
+    fn seal_open() {
+        let key = GeneTokenKey::new(b"secret");
+        let gene =
+            Gene { id: GeneId(42), iter: 3, pepper: [1, 2, 3], server: 7 };
+
+        let token = key.seal(&gene);
+        assert_eq!(token.len(), GENE_TOKEN_LEN);
+        assert!(
+            token
+                .bytes()
+                .all(|c| c.is_ascii_alphanumeric() || c == b'-' || c == b'_')
+        );
+        assert_eq!(token, key.seal(&gene));
+
+        let open = key.open(&token).expect("open");
+        assert_eq!(open, gene);
+        assert_eq!(open.server, gene.server);
+
+        let next = Gene { id: GeneId(43), ..gene };
+        assert_ne!(key.seal(&next)[..22], token[..22]);
+
+        let other = GeneTokenKey::new(b"other secret");
+        assert!(other.open(&token).is_err());
+    }
+
+    #[test]
+    fn strict() {
+        let key = GeneTokenKey::new(b"secret");
+        let gene =
+            Gene { id: GeneId(9), iter: 0, pepper: [9, 8, 7], server: 1 };
+        let token = key.seal(&gene);
+
+        assert!(key.open(&token[1..]).is_err());
+        assert!(key.open(&format!("{token}A")).is_err());
+        assert!(key.open(&format!("{}=", &token[1..])).is_err());
+
+        for i in 0..token.len() {
+            let mut bad = token.clone().into_bytes();
+            bad[i] = if bad[i] == b'B' { b'C' } else { b'B' };
+            let bad = String::from_utf8(bad).unwrap();
+            assert!(key.open(&bad).is_err(), "tampered at {i}");
+        }
+    }
+}
@@ -22,9 +22,35 @@ pub(crate) fn getrandom(buf: &mut [u8]) {
     unsafe { libc::getrandom(ptr as *mut libc::c_void, buf.len(), 0) };
 }
 
+/// milliseconds since the unix epoch
+pub(crate) fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
 pub(crate) fn falloc(file: &File, off: u64, len: u64) -> Result<(), ShahError> {
     let fd = file.as_raw_fd();
     let res = unsafe { libc::posix_fallocate64(fd, off as i64, len as i64) };
+    falloc_result(res)
+}
+
+/// give the disk blocks of the range back to the filesystem.
+/// the file size stays the same and the range reads as zeros
+pub(crate) fn punch_hole(
+    file: &File, off: u64, len: u64,
+) -> Result<(), ShahError> {
+    let fd = file.as_raw_fd();
+    let mode = libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE;
+    let res = unsafe { libc::fallocate64(fd, mode, off as i64, len as i64) };
+    if res == 0 {
+        return Ok(());
+    }
+    falloc_result(io::Error::last_os_error().raw_os_error().unwrap_or(-1))
+}
+
+fn falloc_result(res: i32) -> Result<(), ShahError> {
     if res == 0 {
         return Ok(());
     }